@@ -0,0 +1,133 @@
+//! End-to-end throughput of the difference table on synthetic alignments.
+//!
+//! Alignment sizes default to [`SIZES`] and can be set as queries by codons, e.g.,
+//! `AADIFF_BENCH_SIZES=1000x600,10000x1700 cargo bench`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Numbers of queries and codons of the alignments benchmarked by default.
+const SIZES: [(usize, usize); 3] = [(100, 600), (1_000, 600), (1_000, 10_000)];
+
+/// The fraction of query bases that differ from the reference.
+const MUTATION_RATE: f64 = 0.01;
+
+/// Options each alignment is diffed with.
+const MODES: [(&str, &[&str]); 4] = [
+    ("default", &[]),
+    ("json", &["-f", "json"]),
+    ("low-memory", &["--low-memory"]),
+    ("mmap", &["--mmap"]),
+];
+
+/// A small, seeded generator, so every run benchmarks the same alignments.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 33
+    }
+
+    fn base(&mut self) -> u8 {
+        b"ACGT"[(self.next() % 4) as usize]
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        (self.next() as f64) < p * (1u64 << 31) as f64
+    }
+}
+
+fn sizes() -> Vec<(usize, usize)> {
+    let Ok(sizes) = std::env::var("AADIFF_BENCH_SIZES") else {
+        return SIZES.to_vec();
+    };
+    sizes
+        .split(',')
+        .map(|size| {
+            let parsed = size
+                .split_once('x')
+                .and_then(|(queries, codons)| Some((queries.trim().parse().ok()?, codons.trim().parse().ok()?)));
+            parsed.unwrap_or_else(|| panic!("'{size}' is not a size such as 1000x600"))
+        })
+        .collect()
+}
+
+/// Writes a reference without stop codons followed by `queries` copies of it with
+/// random point mutations, returning the path and its size in bytes.
+fn write_alignment(queries: usize, codons: usize) -> (PathBuf, u64) {
+    let path = std::env::temp_dir().join(format!("aadiff-bench-{queries}x{codons}.fasta"));
+    let mut rng = Lcg(0x5EED ^ (queries * codons) as u64);
+
+    let mut reference = Vec::with_capacity(codons * 3);
+    while reference.len() < codons * 3 {
+        let codon = [rng.base(), rng.base(), rng.base()];
+        if !matches!(&codon, b"TAA" | b"TAG" | b"TGA") {
+            reference.extend_from_slice(&codon);
+        }
+    }
+
+    let mut writer = BufWriter::new(File::create(&path).expect("Could not create the benchmark alignment"));
+    let mut write = |name: &str, sequence: &[u8]| -> std::io::Result<()> {
+        writeln!(writer, ">{name}")?;
+        writer.write_all(sequence)?;
+        writeln!(writer)
+    };
+    write("reference", &reference).expect("Could not write the benchmark alignment");
+    for q in 0..queries {
+        let query = reference
+            .iter()
+            .map(|&b| if rng.chance(MUTATION_RATE) { rng.base() } else { b })
+            .collect::<Vec<_>>();
+        write(&format!("query{q}"), &query).expect("Could not write the benchmark alignment");
+    }
+    drop(writer);
+
+    let size = std::fs::metadata(&path)
+        .expect("Could not read the benchmark alignment")
+        .len();
+    (path, size)
+}
+
+fn run(input: &Path, output: &Path, options: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_aadiff"))
+        .arg("-i")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .args(options)
+        .status()
+        .expect("Could not run aadiff");
+    assert!(status.success(), "aadiff failed with {options:?}");
+}
+
+fn diff(c: &mut Criterion) {
+    let output = std::env::temp_dir().join("aadiff-bench.out");
+    let mut group = c.benchmark_group("diff");
+    group.sample_size(10);
+
+    for (queries, codons) in sizes() {
+        let (input, bytes) = write_alignment(queries, codons);
+        group.throughput(Throughput::Bytes(bytes));
+        for (mode, options) in MODES {
+            group.bench_with_input(BenchmarkId::new(mode, format!("{queries}x{codons}")), &input, |b, input| {
+                b.iter(|| run(input, &output, options));
+            });
+        }
+        let _ = std::fs::remove_file(input);
+    }
+
+    group.finish();
+    let _ = std::fs::remove_file(output);
+}
+
+criterion_group!(benches, diff);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+#ifndef AADIFF_H
+#define AADIFF_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Called once with the column names, reference first and followed by any extra
+ * columns.
+ */
+typedef void (*AadiffHeaderCallback)(const char *const *columns, size_t n_columns, void *user_data);
+
+/**
+ * The options of a run, given as they are written on the command line but without
+ * the program name, e.g., `{"-i", "alignment.fasta", "-r"}`.
+ */
+typedef struct AadiffConfig {
+  const char *const *args;
+  size_t n_args;
+  /**
+   * May be null.
+   */
+  AadiffHeaderCallback header;
+  /**
+   * Passed through to the callbacks untouched.
+   */
+  void *user_data;
+} AadiffConfig;
+
+/**
+ * A row of the difference table, valid only for the duration of the callback.
+ * Empty cells are null.
+ */
+typedef struct AadiffRow {
+  const char *label;
+  const char *reference;
+  const char *const *cells;
+  size_t n_cells;
+  const char *const *extras;
+  size_t n_extras;
+} AadiffRow;
+
+/**
+ * Called once per row of the difference table. Returning nonzero stops the run.
+ */
+typedef int (*AadiffRowCallback)(const struct AadiffRow *row, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Runs `aadiff` with `config`, passing each row of the difference table to
+ * `callback` instead of writing it. Returns 0 on success, 1 if the run failed, in
+ * which case [`aadiff_last_error`] describes why, and 2 if `callback` stopped it.
+ * A null `config` or `callback` fails the run. Progress bars are never drawn.
+ *
+ * # Safety
+ *
+ * `config` must be null or point to a valid [`AadiffConfig`] whose `args` holds
+ * `n_args` NUL-terminated strings.
+ */
+int aadiff_run(const struct AadiffConfig *config, AadiffRowCallback callback);
+
+/**
+ * The reason the last failed [`aadiff_run`] on this thread failed, or null. Valid
+ * until the next run on the same thread.
+ */
+const char *aadiff_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* AADIFF_H */
@@ -0,0 +1,248 @@
+use crate::{
+    data::{BLOSUM62, BLOSUM62_ORDER},
+    translate::translate_codon,
+};
+
+const GAP_OPEN: i32 = -11;
+const GAP_EXTEND: i32 = -1;
+
+/// A single column of a pairwise protein alignment, by residue index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Match(usize, usize),
+    Deletion(usize),
+    Insertion(usize),
+}
+
+/// Codon-aware multiple alignment of unaligned coding sequences against a reference.
+///
+/// Each query is translated and aligned to the translated reference at the protein
+/// level, then its codons are threaded back onto the alignment so that every indel
+/// spans whole codons. The pairwise alignments are merged by padding each reference
+/// residue with enough gap codons for the longest insertion any query has there.
+/// Sequences are read in frame from their first base and any trailing partial codon
+/// is dropped.
+pub(crate) fn codon_align(reference: &[u8], queries: &[&[u8]]) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let ref_codons = codons(reference);
+    let ref_aa = ref_codons.iter().map(|&c| translate_codon(c)).collect::<Vec<_>>();
+
+    let layouts = queries
+        .iter()
+        .map(|query| {
+            let query_codons = codons(query);
+            let query_aa = query_codons.iter().map(|&c| translate_codon(c)).collect::<Vec<_>>();
+
+            let mut aligned = vec![None; ref_aa.len()];
+            let mut inserted = vec![Vec::new(); ref_aa.len() + 1];
+            let mut anchor = 0;
+            for op in align_residues(&ref_aa, &query_aa) {
+                match op {
+                    Op::Match(i, j) => {
+                        aligned[i] = Some(query_codons[j]);
+                        anchor = i + 1;
+                    }
+                    Op::Deletion(i) => anchor = i + 1,
+                    Op::Insertion(j) => inserted[anchor].push(query_codons[j]),
+                }
+            }
+            (aligned, inserted)
+        })
+        .collect::<Vec<_>>();
+
+    let max_inserted = (0..=ref_aa.len())
+        .map(|k| layouts.iter().map(|(_, inserted)| inserted[k].len()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+
+    let mut aligned_reference = Vec::new();
+    for (k, &width) in max_inserted.iter().enumerate() {
+        if k > 0 {
+            aligned_reference.extend_from_slice(&ref_codons[k - 1]);
+        }
+        aligned_reference.extend(std::iter::repeat_n(b'-', width * 3));
+    }
+
+    let aligned_queries = layouts
+        .into_iter()
+        .map(|(aligned, inserted)| {
+            let mut sequence = Vec::with_capacity(aligned_reference.len());
+            for (k, &width) in max_inserted.iter().enumerate() {
+                if k > 0 {
+                    sequence.extend_from_slice(&aligned[k - 1].unwrap_or(*b"---"));
+                }
+                for codon in &inserted[k] {
+                    sequence.extend_from_slice(codon);
+                }
+                sequence.extend(std::iter::repeat_n(b'-', (width - inserted[k].len()) * 3));
+            }
+            sequence
+        })
+        .collect();
+
+    (aligned_reference, aligned_queries)
+}
+
+/// Splits a sequence into codons after removing any existing alignment gaps.
+fn codons(sequence: &[u8]) -> Vec<[u8; 3]> {
+    let ungapped = sequence
+        .iter()
+        .copied()
+        .filter(|&b| b != b'-' && b != b'.')
+        .collect::<Vec<_>>();
+    ungapped.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+/// BLOSUM62 score of two residues, with anything unrecognized scored as `X`.
+pub(crate) fn score(a: u8, b: u8) -> i32 {
+    let index = |aa: u8| {
+        BLOSUM62_ORDER
+            .iter()
+            .position(|&x| x == aa)
+            .unwrap_or(BLOSUM62_ORDER.len() - 2)
+    };
+    i32::from(BLOSUM62[index(a)][index(b)])
+}
+
+/// Globally aligns two protein sequences with affine gaps (Gotoh), without penalizing
+/// leading or trailing gaps in either sequence.
+fn align_residues(reference: &[u8], query: &[u8]) -> Vec<Op> {
+    const FROM_DIAGONAL: u8 = 0;
+    const FROM_DELETION: u8 = 1;
+    const FROM_INSERTION: u8 = 2;
+    const DELETION_EXTENDED: u8 = 4;
+    const INSERTION_EXTENDED: u8 = 8;
+
+    let (n, m) = (reference.len(), query.len());
+    let width = m + 1;
+    let mut best = vec![0i32; (n + 1) * width];
+    let mut deletion = vec![i32::MIN / 2; (n + 1) * width];
+    let mut insertion = vec![i32::MIN / 2; (n + 1) * width];
+    let mut trace = vec![FROM_DIAGONAL; (n + 1) * width];
+
+    for i in 1..=n {
+        for j in 1..=m {
+            let here = i * width + j;
+            let up = (i - 1) * width + j;
+            let left = i * width + j - 1;
+            let diagonal = (i - 1) * width + j - 1;
+            let mut t = 0;
+
+            deletion[here] = if deletion[up] + GAP_EXTEND > best[up] + GAP_OPEN {
+                t |= DELETION_EXTENDED;
+                deletion[up] + GAP_EXTEND
+            } else {
+                best[up] + GAP_OPEN
+            };
+
+            insertion[here] = if insertion[left] + GAP_EXTEND > best[left] + GAP_OPEN {
+                t |= INSERTION_EXTENDED;
+                insertion[left] + GAP_EXTEND
+            } else {
+                best[left] + GAP_OPEN
+            };
+
+            let matched = best[diagonal] + score(reference[i - 1], query[j - 1]);
+            best[here] = if matched >= deletion[here] && matched >= insertion[here] {
+                matched
+            } else if deletion[here] >= insertion[here] {
+                t |= FROM_DELETION;
+                deletion[here]
+            } else {
+                t |= FROM_INSERTION;
+                insertion[here]
+            };
+            trace[here] = t;
+        }
+    }
+
+    let (mut i, mut j) = (0..=m)
+        .map(|j| (n, j))
+        .chain((0..=n).map(|i| (i, m)))
+        .max_by_key(|&(i, j)| best[i * width + j])
+        .unwrap_or((n, m));
+
+    let mut ops = (j..m).rev().map(Op::Insertion).collect::<Vec<_>>();
+    ops.extend((i..n).rev().map(Op::Deletion));
+
+    let mut state = FROM_DIAGONAL;
+    while i > 0 && j > 0 {
+        let t = trace[i * width + j];
+        if state == FROM_DIAGONAL {
+            state = t & 3;
+        }
+
+        match state {
+            FROM_DELETION => {
+                ops.push(Op::Deletion(i - 1));
+                if t & DELETION_EXTENDED == 0 {
+                    state = FROM_DIAGONAL;
+                }
+                i -= 1;
+            }
+            FROM_INSERTION => {
+                ops.push(Op::Insertion(j - 1));
+                if t & INSERTION_EXTENDED == 0 {
+                    state = FROM_DIAGONAL;
+                }
+                j -= 1;
+            }
+            _ => {
+                ops.push(Op::Match(i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            }
+        }
+    }
+    ops.extend((0..i).rev().map(Op::Deletion));
+    ops.extend((0..j).rev().map(Op::Insertion));
+
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::back_translate;
+
+    const PROTEIN: &[u8] = b"MKWVTFISLLFLFSSAYS";
+
+    fn coding(protein: &[u8]) -> Vec<u8> {
+        protein.iter().flat_map(|&aa| back_translate(aa)).collect()
+    }
+
+    #[test]
+    fn opens_one_gap_for_a_deleted_residue() {
+        let query = [&PROTEIN[..4], &PROTEIN[5..]].concat();
+        let ops = align_residues(PROTEIN, &query);
+        assert_eq!(ops.len(), PROTEIN.len());
+        assert_eq!(ops[4], Op::Deletion(4));
+        assert!(ops[..4].iter().enumerate().all(|(i, &op)| op == Op::Match(i, i)));
+        assert!(ops[5..].iter().enumerate().all(|(k, &op)| op == Op::Match(k + 5, k + 4)));
+    }
+
+    #[test]
+    fn leaves_end_gaps_unpenalized() {
+        let ops = align_residues(PROTEIN, &PROTEIN[3..15]);
+        assert_eq!(&ops[..3], [Op::Deletion(0), Op::Deletion(1), Op::Deletion(2)]);
+        assert!(ops[3..15].iter().enumerate().all(|(k, &op)| op == Op::Match(k + 3, k)));
+        assert_eq!(&ops[15..], [Op::Deletion(15), Op::Deletion(16), Op::Deletion(17)]);
+    }
+
+    #[test]
+    fn threads_codons_around_whole_codon_indels() {
+        let reference = coding(PROTEIN);
+        let deleted = coding(&[&PROTEIN[..4], &PROTEIN[5..]].concat());
+        let inserted = coding(&[&PROTEIN[..9], b"W", &PROTEIN[9..]].concat());
+        let (aligned_reference, aligned) = codon_align(&reference, &[&deleted, &inserted]);
+
+        // The reference is padded with a gap codon for the insertion, after residue 9.
+        let expected_reference = [&reference[..27], b"---", &reference[27..]].concat();
+        assert_eq!(aligned_reference, expected_reference);
+        // The query missing residue 4 is gapped there, and at the other's insertion.
+        assert_eq!(
+            aligned[0],
+            [&deleted[..12], b"---", &deleted[12..24], b"---", &deleted[24..]].concat()
+        );
+        assert_eq!(aligned[1], inserted);
+    }
+}
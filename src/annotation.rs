@@ -0,0 +1,288 @@
+use crate::{
+    error::{AadiffError, Result},
+    numbering::parse_position_range,
+};
+use std::{collections::HashMap, ops::RangeInclusive, path::Path};
+
+/// Labeled ranges of positions, e.g., protein regions or antigenic sites.
+pub(crate) struct Regions(Vec<(RangeInclusive<isize>, String)>);
+
+impl Regions {
+    /// Reads a tab-separated file of `start`, `end`, and `label` columns, or of a
+    /// range such as `100-260` and a label. Blank lines, `#` comments, and a header
+    /// line are skipped.
+    pub(crate) fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+            kind: "annotation file",
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut regions = Vec::new();
+        for (n, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').map(str::trim).collect::<Vec<_>>();
+            let parsed = match fields.as_slice() {
+                [start, end, label, ..] => parse_position_range(&format!("{start}-{end}")).map(|r| (r, label)),
+                [range, label] => parse_position_range(range).map(|r| (r, label)),
+                _ => Err("expected a range and a label".to_string()),
+            };
+
+            match parsed {
+                Ok((range, label)) => regions.push((range, label.to_string())),
+                Err(_) if regions.is_empty() => {}
+                Err(message) => {
+                    return Err(AadiffError::Parse {
+                        kind: "Annotation file",
+                        path: path.to_path_buf(),
+                        line: n + 1,
+                        message,
+                    });
+                }
+            }
+        }
+        Ok(Regions(regions))
+    }
+
+    /// Reads protein domains from InterProScan TSV output, labeling each by its
+    /// signature description, or its accession if it has none. Files with fewer
+    /// columns are read as [`Regions::read`] does.
+    pub(crate) fn read_domains(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+            kind: "domain file",
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut domains = Vec::new();
+        for (n, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').map(str::trim).collect::<Vec<_>>();
+            if fields.len() < 8 {
+                return Regions::read(path);
+            }
+
+            let label = match fields[5] {
+                "" | "-" => fields[4],
+                description => description,
+            };
+            match parse_position_range(&format!("{}-{}", fields[6], fields[7])) {
+                Ok(range) => domains.push((range, label.to_string())),
+                Err(message) => {
+                    return Err(AadiffError::Parse {
+                        kind: "Domain file",
+                        path: path.to_path_buf(),
+                        line: n + 1,
+                        message,
+                    });
+                }
+            }
+        }
+        Ok(Regions(domains))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn name(&self, index: usize) -> &str {
+        &self.0[index].1
+    }
+
+    /// Indices of the ranges covering `position`.
+    pub(crate) fn covering(&self, position: isize) -> impl Iterator<Item = usize> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(move |(_, (range, _))| range.contains(&position))
+            .map(|(index, _)| index)
+    }
+
+    /// Every label whose range covers `position`, joined with `;`.
+    pub(crate) fn label(&self, position: isize) -> Option<String> {
+        let labels = self.covering(position).map(|index| self.name(index)).collect::<Vec<_>>();
+        (!labels.is_empty()).then(|| labels.join(";"))
+    }
+}
+
+/// Assignment of query sequences to metadata groups, e.g., clades.
+pub(crate) struct Groups {
+    names:   Vec<String>,
+    members: Vec<Option<usize>>,
+}
+
+impl Groups {
+    /// Reads a two-column file of sequence names and group labels, separated by a tab
+    /// or comma, and assigns each of `sequences` to its group. Groups are ordered by
+    /// their first member; sequences not listed belong to no group.
+    pub(crate) fn read<'a>(path: &Path, sequences: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+            kind: "group file",
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut labels = HashMap::new();
+        for (n, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((name, group)) = line.split_once('\t').or_else(|| line.split_once(',')) else {
+                return Err(AadiffError::Parse {
+                    kind:    "Group file",
+                    path:    path.to_path_buf(),
+                    line:    n + 1,
+                    message: "expected a sequence name and a group".to_string(),
+                });
+            };
+            labels.insert(name.trim().to_string(), group.trim().to_string());
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        let members = sequences
+            .map(|sequence| {
+                let group = labels.get(sequence)?;
+                Some(names.iter().position(|g| g == group).unwrap_or_else(|| {
+                    names.push(group.clone());
+                    names.len() - 1
+                }))
+            })
+            .collect();
+        Ok(Groups { names, members })
+    }
+
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Gives each group's frequency of every observed value, most common first, e.g.,
+    /// `S:0.75;N:0.25`. Sequences without an observation are not counted.
+    pub(crate) fn frequencies<'a>(&self, observed: impl Iterator<Item = Option<&'a str>>) -> Vec<Option<String>> {
+        let mut counts = vec![Vec::<(&str, usize)>::new(); self.names.len()];
+        for (group, value) in self.members.iter().zip(observed) {
+            let (Some(group), Some(value)) = (group, value) else {
+                continue;
+            };
+
+            match counts[*group].iter_mut().find(|(v, _)| *v == value) {
+                Some((_, n)) => *n += 1,
+                None => counts[*group].push((value, 1)),
+            }
+        }
+
+        counts
+            .into_iter()
+            .map(|mut values| {
+                let total = values.iter().map(|(_, n)| n).sum::<usize>();
+                values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+                (total > 0).then(|| {
+                    values
+                        .iter()
+                        .map(|(v, n)| format!("{v}:{:.2}", *n as f64 / total as f64))
+                        .collect::<Vec<_>>()
+                        .join(";")
+                })
+            })
+            .collect()
+    }
+}
+
+/// A mutation of interest, e.g., `NA:H275Y`, matched by position label and residue.
+struct WatchedMutation {
+    name: String,
+    /// The gene or segment the position is in, e.g., `NA`
+    gene:      Option<String>,
+    reference: Option<u8>,
+    position:  String,
+    residue:   String,
+}
+
+/// Mutations of interest to flag wherever a query carries them.
+pub(crate) struct Watchlist(Vec<WatchedMutation>);
+
+impl Watchlist {
+    /// Reads one mutation per line, written as the reference residue, position, and
+    /// query residue, e.g., `H275Y`, `K158del`, or `Q27*`, optionally prefixed with a
+    /// gene name and `:`. The reference residue may be omitted. Blank lines and `#`
+    /// comments are skipped.
+    pub(crate) fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+            kind: "watchlist",
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mutations = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                parse_mutation(line).ok_or_else(|| {
+                    AadiffError::Invalid(format!("Invalid mutation in watchlist '{}': '{line}'", path.display()))
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Watchlist(mutations))
+    }
+
+    /// The first watched mutation given with a gene, if any.
+    pub(crate) fn first_with_gene(&self) -> Option<&str> {
+        self.0.iter().find(|m| m.gene.is_some()).map(|m| m.name.as_str())
+    }
+
+    /// The name of the watched mutation matching a query cell, if any. A mutation
+    /// given with a gene matches the label of that gene's position, e.g., `NA:275`
+    /// in multi-segment mode, and one without matches the position in any gene.
+    /// Cells listing several possible residues, with or without their probabilities,
+    /// match when any of them does.
+    pub(crate) fn find(&self, label: &str, reference: u8, cell: &str) -> Option<&str> {
+        let position = label.rsplit(':').next().unwrap_or(label);
+        self.0
+            .iter()
+            .find(|m| {
+                let at = match &m.gene {
+                    Some(gene) => label.strip_prefix(gene.as_str()).and_then(|p| p.strip_prefix(':')) == Some(&m.position),
+                    None => m.position == position,
+                };
+                at && m.reference.is_none_or(|r| r == reference)
+                    && (cell == m.residue || cell.split('/').any(|aa| aa.split('(').next() == Some(&m.residue)))
+            })
+            .map(|m| m.name.as_str())
+    }
+}
+
+fn parse_mutation(name: &str) -> Option<WatchedMutation> {
+    let (gene, mutation) = match name.rsplit_once(':') {
+        Some((gene, mutation)) => (Some(gene.to_string()), mutation),
+        None => (None, name),
+    };
+    let (rest, residue) = if mutation.to_ascii_lowercase().ends_with("del") {
+        (&mutation[..mutation.len() - 3], "del".to_string())
+    } else {
+        let (i, last) = mutation.char_indices().last()?;
+        (&mutation[..i], last.to_ascii_uppercase().to_string())
+    };
+
+    let (reference, position) = match rest.as_bytes().first()? {
+        r if r.is_ascii_alphabetic() || *r == b'*' => (Some(r.to_ascii_uppercase()), &rest[1..]),
+        _ => (None, rest),
+    };
+    if position.is_empty() {
+        return None;
+    }
+
+    Some(WatchedMutation {
+        name: name.to_string(),
+        gene,
+        reference,
+        position: position.to_string(),
+        residue,
+    })
+}
@@ -0,0 +1,360 @@
+use crate::{
+    APDArgs, Command, DiffArgs, Io, config, diff,
+    error::{AadiffError, Result},
+    open_error, open_output,
+    output::Format,
+    provenance::sha256,
+};
+use clap::FromArgMatches;
+use flate2::{Compression, write::GzEncoder};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+use tracing::{error, info};
+
+/// The extensions of the files read as FASTA from the directory of a batch.
+const FASTA_EXTENSIONS: [&str; 6] = ["fasta", "fa", "fas", "fna", "ffn", "fsa"];
+
+/// The file in the output directory listing the inputs whose tables are complete,
+/// which `--resume` skips.
+const MANIFEST: &str = "aadiff-manifest.tsv";
+
+/// The tables of a batch written so far, by file name, each with the checksum of
+/// the input it was written from.
+type Completed = HashMap<String, String>;
+
+/// Writes the difference table of every FASTA file in the directory given to
+/// `aadiff batch` to a file of its own in the output directory, diffing up to
+/// `--jobs` inputs at once. An input that fails is reported and the others carry
+/// on, but the batch then fails once they are done. Each table is recorded in the
+/// manifest once complete, so that a batch resumed with `--resume` skips it.
+pub(crate) fn batch(args: APDArgs) -> Result<()> {
+    let Some(Command::Batch {
+        dir,
+        out_dir,
+        jobs,
+        gzip,
+        overwrite,
+        resume,
+        diff,
+    }) = &args.command
+    else {
+        return Ok(());
+    };
+    check_shared_outputs(&args, diff)?;
+    let extension = extension(&args, diff, *gzip);
+    let inputs = inputs(dir)?;
+    if inputs.is_empty() {
+        return Err(AadiffError::Empty(format!("No FASTA files found in '{}'.", dir.display())));
+    }
+    let outputs = outputs(&inputs, out_dir, &extension)?;
+    // Tables left by an interrupted batch are incomplete, and replaced on resuming.
+    if !overwrite
+        && !resume
+        && let Some(existing) = outputs.iter().find(|output| output.exists())
+    {
+        return Err(AadiffError::Invalid(format!(
+            "'{}' already exists; give --overwrite to replace the tables in '{}', or --resume to finish the batch that wrote them.",
+            existing.display(),
+            out_dir.display()
+        )));
+    }
+    std::fs::create_dir_all(out_dir).map_err(open_error(out_dir))?;
+    let manifest_path = out_dir.join(MANIFEST);
+    let completed = if *resume {
+        read_manifest(&manifest_path)?
+    } else {
+        Completed::new()
+    };
+    let manifest = Mutex::new(open_manifest(&manifest_path, *resume)?);
+
+    // Each run also works through its queries in parallel, on the same threads.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.get())
+        .build()
+        .map_err(|e| AadiffError::Invalid(format!("Could not start the thread pool: {e}")))?;
+    info!("Diffing {} inputs, {} at a time", inputs.len(), jobs);
+    let failed = pool.install(|| {
+        inputs
+            .par_iter()
+            .zip(outputs.par_iter())
+            .map(|(input, output)| {
+                let result = resume_input(input, output, *gzip, &completed, &manifest);
+                match &result {
+                    Ok(true) => info!("Wrote '{}'", output.display()),
+                    Ok(false) => info!("Skipping '{}', whose table is complete", input.display()),
+                    Err(e) => error!("Could not diff '{}': {e}", input.display()),
+                }
+                result
+            })
+            .filter(Result::is_err)
+            .count()
+    });
+    match failed {
+        0 => Ok(()),
+        n => Err(AadiffError::Invalid(format!(
+            "{n} of {} inputs could not be diffed.",
+            inputs.len()
+        ))),
+    }
+}
+
+/// Refuses the options naming a single input or output, which every input of the
+/// batch would otherwise read or overwrite, and `--threads`, as the inputs share
+/// the batch's threads.
+fn check_shared_outputs(args: &APDArgs, diff: &DiffArgs) -> Result<()> {
+    if args.threads.is_some() {
+        return Err(AadiffError::Invalid(
+            "--threads cannot be given to a batch, whose inputs share its --jobs threads.".to_string(),
+        ));
+    }
+    let shared = [
+        ("--input-fasta", args.input_fasta.is_some()),
+        ("--output-xsv", args.output_xsv.is_some()),
+        ("--output-csv", diff.output_csv.is_some()),
+        ("--output-json", diff.output_json.is_some()),
+        ("--output-summary", diff.output_summary.is_some()),
+        ("--emergence", diff.emergence.is_some()),
+        ("--profile", diff.profile.is_some()),
+        ("--watch", args.watch),
+        ("--validate", args.validate),
+    ];
+    match shared.iter().find(|(_, given)| *given) {
+        Some((flag, _)) => Err(AadiffError::Invalid(format!(
+            "{flag} cannot be given to a batch, whose inputs are the FASTA files of its directory, each written to its own table."
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The extension of the tables written, by their format, e.g., `tsv.gz` for
+/// tab-separated tables compressed with `--gzip`.
+fn extension(args: &APDArgs, diff: &DiffArgs, gzip: bool) -> String {
+    let extension = match diff.output_format {
+        Format::Csv if args.output_delimiter.as_deref() == Some("\t") => "tsv",
+        Format::Csv => "csv",
+        Format::Json => "json",
+        Format::Summary => "txt",
+    };
+    if gzip {
+        format!("{extension}.gz")
+    } else {
+        extension.to_string()
+    }
+}
+
+/// The FASTA files directly within `dir`, by name.
+fn inputs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = std::fs::read_dir(dir).map_err(open_error(dir))?;
+    let mut inputs = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(open_error(dir))?.path();
+        let is_fasta = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| FASTA_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if is_fasta && path.is_file() {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// The table each input is written to, named for it, e.g., `out/H3.csv` for
+/// `H3.fasta`. Inputs differing only in their extension would be written to the
+/// same table, and are refused.
+fn outputs(inputs: &[PathBuf], out_dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut named = HashMap::new();
+    let manifest = out_dir.join(MANIFEST);
+    let mut outputs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut name = input.file_stem().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(extension);
+        let output = out_dir.join(name);
+        if output == manifest {
+            return Err(AadiffError::Invalid(format!(
+                "'{}' would be written over the batch's manifest; rename it.",
+                input.display()
+            )));
+        }
+        if let Some(earlier) = named.insert(output.clone(), input) {
+            return Err(AadiffError::Invalid(format!(
+                "'{}' and '{}' would both be written to '{}'; rename one.",
+                earlier.display(),
+                input.display(),
+                output.display()
+            )));
+        }
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
+
+/// Diffs one input of the batch unless its table is complete from an earlier run of
+/// it on the same input, recording the table in the manifest once written. Gives
+/// whether the input was diffed.
+fn resume_input(input: &Path, output: &Path, gzip: bool, completed: &Completed, manifest: &Mutex<File>) -> Result<bool> {
+    let checksum = sha256(input).map_err(|source| AadiffError::Read {
+        kind: "input to checksum",
+        path: input.to_path_buf(),
+        source,
+    })?;
+    let table = file_name(output);
+    if completed.get(&table) == Some(&checksum) && output.exists() {
+        return Ok(false);
+    }
+    diff_input(input, output, gzip)?;
+    let line = format!("{}\t{checksum}\t{table}\n", file_name(input));
+    let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+    manifest.write_all(line.as_bytes()).map_err(AadiffError::Output)?;
+    Ok(true)
+}
+
+/// Diffs one input of the batch into its table, compressing it as it is written
+/// with `--gzip`.
+fn diff_input(input: &Path, output: &Path, gzip: bool) -> Result<()> {
+    let args = args_for(input)?;
+    let mut file = BufWriter::new(open_output(output, true, false)?);
+    if gzip {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        diff(args, Io::Output(&mut encoder))?;
+        encoder
+            .finish()
+            .and_then(|mut file| file.flush())
+            .map_err(AadiffError::Output)
+    } else {
+        diff(args, Io::Output(&mut file))?;
+        file.flush().map_err(AadiffError::Output)
+    }
+}
+
+/// Opens the manifest to add to as tables are completed, starting it anew unless
+/// resuming.
+fn open_manifest(path: &Path, resume: bool) -> Result<File> {
+    let mut options = OpenOptions::new();
+    if resume {
+        options.append(true).create(true);
+    } else {
+        options.write(true).create(true).truncate(true);
+    }
+    let mut file = options.open(path).map_err(open_error(path))?;
+    if file.metadata().map_err(open_error(path))?.len() == 0 {
+        file.write_all(b"input\tsha256\ttable\n").map_err(AadiffError::Output)?;
+    }
+    Ok(file)
+}
+
+/// Reads the tables completed by earlier runs of a batch from its manifest, if
+/// any. Lines cut short by an interruption are passed over, and a table recorded
+/// more than once keeps its latest checksum.
+fn read_manifest(path: &Path) -> Result<Completed> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Completed::new()),
+        Err(source) => {
+            return Err(AadiffError::Read {
+                kind: "batch manifest",
+                path: path.to_path_buf(),
+                source,
+            });
+        }
+    };
+    Ok(text
+        .lines()
+        .skip(1)
+        .filter_map(|line| match line.split('\t').collect::<Vec<_>>()[..] {
+            [_, checksum, table] if checksum.len() == 64 => Some((table.to_string(), checksum.to_string())),
+            _ => None,
+        })
+        .collect())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// The arguments of the run on one input: those of the command line, parsed anew
+/// as `--watch` does, with the diff options given to the batch and the input in
+/// place.
+fn args_for(input: &Path) -> Result<APDArgs> {
+    let argv = config::with_config(std::env::args_os())?;
+    let matches = config::command()
+        .try_get_matches_from(argv)
+        .map_err(|e| AadiffError::Invalid(e.to_string()))?;
+    let mut args = APDArgs::from_arg_matches(&matches).map_err(|e| AadiffError::Invalid(e.to_string()))?;
+    if let Some(Command::Batch { diff, .. }) = args.command.take() {
+        args.diff = *diff;
+    }
+    args.input_fasta = Some(input.to_path_buf());
+    args.no_progress = true;
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_tables_for_their_inputs() {
+        let dir = std::env::temp_dir().join(format!("aadiff-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.fa", "a.fasta", "notes.txt", "c.FNA"] {
+            std::fs::write(dir.join(name), ">ref\nATG\n").unwrap();
+        }
+
+        let inputs = inputs(&dir).unwrap();
+        let names = inputs
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.fasta", "b.fa", "c.FNA"]);
+        let tables = outputs(&inputs, Path::new("out"), "csv.gz").unwrap();
+        assert_eq!(tables[0], Path::new("out/a.csv.gz"));
+        assert_eq!(tables[2], Path::new("out/c.csv.gz"));
+
+        // H3.v2.fasta keeps its inner dot.
+        std::fs::write(dir.join("H3.v2.fasta"), ">ref\nATG\n").unwrap();
+        let tables = outputs(&super::inputs(&dir).unwrap(), Path::new("out"), "csv").unwrap();
+        assert_eq!(tables[0], Path::new("out/H3.v2.csv"));
+
+        std::fs::write(dir.join("a.fa"), ">ref\nATG\n").unwrap();
+        assert!(outputs(&super::inputs(&dir).unwrap(), Path::new("out"), "csv").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resumes_from_the_manifest() {
+        let dir = std::env::temp_dir().join(format!("aadiff-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(MANIFEST);
+        let (old, new) = ("0".repeat(64), "1".repeat(64));
+
+        let mut manifest = open_manifest(&path, false).unwrap();
+        writeln!(manifest, "a.fasta\t{old}\ta.csv").unwrap();
+        writeln!(manifest, "b.fasta\t{old}\tb.csv").unwrap();
+        writeln!(manifest, "a.fasta\t{new}\ta.csv").unwrap();
+        // Cut short by an interruption.
+        write!(manifest, "c.fasta\t0123").unwrap();
+        drop(manifest);
+
+        let completed = read_manifest(&path).unwrap();
+        assert_eq!(completed.len(), 2);
+        assert_eq!(completed["a.csv"], new);
+        assert_eq!(completed["b.csv"], old);
+
+        // Resuming adds to the manifest, while a new batch starts it over.
+        drop(open_manifest(&path, true).unwrap());
+        assert_eq!(read_manifest(&path).unwrap().len(), 2);
+        drop(open_manifest(&path, false).unwrap());
+        assert!(read_manifest(&path).unwrap().is_empty());
+        assert!(read_manifest(&dir.join("missing.tsv")).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
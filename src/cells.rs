@@ -0,0 +1,160 @@
+use crate::{
+    APDArgs,
+    align::score,
+    numbering::Positions,
+    properties::property_change,
+    queries::ValidSeq,
+    translate::{PartialCodonPolicy, ambiguity_code, expand_codon, expand_codon_counts, is_partially_gapped, stands_for},
+};
+use zoe::prelude::*;
+
+/// The cell of a query residue that differs from the reference: `del`, the possible
+/// amino acids of a degenerate codon, or the residue itself.
+pub(crate) fn residue_cell(aa: u8, codon: [u8; 3], args: &APDArgs) -> String {
+    if aa == b'-' {
+        "del".to_string()
+    } else if args.protein
+        && let Some(code) = ambiguity_code(codon)
+    {
+        (code as char).to_string()
+    } else if aa == b'X'
+        && codon != *b"NNN"
+        && let Some(degen_aa) = expand_codon(codon)
+    {
+        // Fully masked codons carry no information, so they stay as X.
+        if args.diff.degenerate_probabilities {
+            format_degenerate_probabilities(codon)
+        } else {
+            format_degenerate(&degen_aa, args.diff.degenerate_cap)
+        }
+    } else {
+        (aa as char).to_string()
+    }
+}
+
+/// Joins the possible amino acids of a degenerate codon, e.g., `I/M/T`, or gives
+/// their count when there are more than `cap`.
+pub(crate) fn format_degenerate(residues: &[u8], cap: Option<usize>) -> String {
+    if let Some(cap) = cap
+        && residues.len() > cap
+    {
+        format!("X({})", residues.len())
+    } else {
+        residues
+            .iter()
+            .map(|&aa| (aa as char).to_string())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Joins the possible amino acids of a degenerate codon with the fraction of its
+/// expansions encoding each, in lowest terms, e.g., `I(2/3)/L(1/3)`.
+pub(crate) fn format_degenerate_probabilities(codon: [u8; 3]) -> String {
+    let gcd = |mut a: usize, mut b: usize| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+
+    let Some((counts, total)) = expand_codon_counts(codon) else {
+        return "X".to_string();
+    };
+    counts
+        .iter()
+        .map(|&(aa, n)| {
+            let d = gcd(n, total);
+            format!("{}({}/{})", aa as char, n / d, total / d)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Joins each sequence's notes into a cell for a trailing summary row, or gives
+/// `None` when no sequence has anything to report.
+pub(crate) fn notes_cells(notes: &[Vec<String>]) -> Option<Vec<Option<String>>> {
+    if notes.iter().all(Vec::is_empty) {
+        return None;
+    }
+
+    Some(notes.iter().map(|n| (!n.is_empty()).then(|| n.join(";"))).collect())
+}
+
+/// The cells of the extra references: each reference's residue, then how each query
+/// differs from it.
+pub(crate) fn extra_cells(
+    args: &APDArgs, i: usize, masked: bool, extra_references: &[(String, AminoAcids)], queries: &[ValidSeq],
+) -> Vec<Option<String>> {
+    extra_references
+        .iter()
+        .flat_map(|(_, baseline)| {
+            let base_aa = baseline[i];
+            std::iter::once(Some((base_aa as char).to_string())).chain(queries.iter().map(move |seq| {
+                let codon = seq.sequence.codon(i);
+                let skipped = args.partial_codon_policy == PartialCodonPolicy::Skip && is_partially_gapped(codon);
+                let unknown = args.diff.ambiguous_matches_reference && seq.sequence.residue(i) == b'X';
+                let ambiguous_match = args.protein && stands_for(codon, base_aa);
+                (seq.compares(i) && seq.sequence.residue(i) != base_aa && !skipped && !unknown && !ambiguous_match).then(
+                    || {
+                        if masked {
+                            "masked".to_string()
+                        } else {
+                            residue_cell(seq.sequence.residue(i), codon, args)
+                        }
+                    },
+                )
+            }))
+        })
+        .collect()
+}
+
+/// The notes on each query's substitution at `i`, by kind: how its properties change,
+/// its BLOSUM62 score, and the bases that changed.
+pub(crate) fn substitution_notes(
+    args: &APDArgs, i: usize, reference: &AminoAcids, ref_codons: &[u8], positions: &Positions, queries: &[ValidSeq],
+    cells: &[Option<String>],
+) -> Vec<(&'static str, Vec<Option<String>>)> {
+    let substitutions = queries
+        .iter()
+        .zip(cells)
+        .map(|(s, cell)| {
+            let aa = s.sequence.residue(i);
+            let substituted = cell.as_deref()?.trim_end_matches(['!', '<', '^']).as_bytes() == [aa];
+            substituted.then_some((reference[i], aa))
+        })
+        .collect::<Vec<_>>();
+
+    let mut notes = Vec::new();
+    if args.diff.properties {
+        let changes = substitutions.iter().map(|s| s.and_then(|(r, q)| property_change(r, q)));
+        notes.push(("properties", changes.collect()));
+    }
+    if args.diff.blosum {
+        let scores = substitutions.iter().map(|s| s.map(|(r, q)| score(r, q).to_string()));
+        notes.push(("blosum", scores.collect()));
+    }
+    if args.diff.nucleotide_changes {
+        let changes = queries.iter().zip(cells).map(|(s, cell)| {
+            let cell = cell.as_deref()?;
+            if ["masked", "missing", "fs", "del"].iter().any(|p| cell.starts_with(p)) {
+                return None;
+            }
+
+            let changes = (i * 3..i * 3 + 3)
+                .filter(|&j| s.sequence.base(j) != ref_codons[j] && s.sequence.base(j) != b'-' && ref_codons[j] != b'-')
+                .map(|j| {
+                    format!(
+                        "{}{}{}",
+                        ref_codons[j] as char,
+                        positions.nucleotide(j),
+                        s.sequence.base(j) as char
+                    )
+                })
+                .collect::<Vec<_>>();
+            (!changes.is_empty()).then(|| changes.join(";"))
+        });
+        notes.push(("nucleotides", changes.collect()));
+    }
+    notes
+}
@@ -0,0 +1,46 @@
+use crate::scan::column_differs;
+
+/// The codons of every query stored position by position, so that the codons all
+/// queries have at one position are contiguous in memory.
+pub(crate) struct Columns {
+    codons:  usize,
+    queries: usize,
+    bases:   Vec<u8>,
+}
+
+impl Columns {
+    /// Gathers the codons of each query for a reference of `codons` codons. Shorter
+    /// queries are padded with gaps, and longer ones are cut off.
+    pub(crate) fn gather(codons: usize, queries: &[&[u8]]) -> Self {
+        let n = queries.len();
+        let mut bases = vec![b'-'; codons * n * 3];
+        for (q, query) in queries.iter().enumerate() {
+            for (i, codon) in query.chunks_exact(3).take(codons).enumerate() {
+                let start = (i * n + q) * 3;
+                bases[start..start + 3].copy_from_slice(codon);
+            }
+        }
+        Columns {
+            codons,
+            queries: n,
+            bases,
+        }
+    }
+
+    /// The number of codons in each query.
+    pub(crate) fn len(&self) -> usize {
+        self.codons
+    }
+
+    /// The bases of codon `i` of query `q`.
+    pub(crate) fn codon(&self, i: usize, q: usize) -> [u8; 3] {
+        let start = (i * self.queries + q) * 3;
+        [self.bases[start], self.bases[start + 1], self.bases[start + 2]]
+    }
+
+    /// Whether any query has a codon other than `codon` at position `i`.
+    pub(crate) fn differs(&self, i: usize, codon: [u8; 3]) -> bool {
+        let width = self.queries * 3;
+        column_differs(&self.bases[i * width..(i + 1) * width], codon)
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    APDArgs,
+    error::{AadiffError, Result},
+    output::push_field,
+    records::{read_all, report_skipped},
+    translate::translate,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+use tracing::warn;
+use zoe::{data::fasta::FastaNT, prelude::*};
+
+/// Writes every aligned position where a sequence present in both alignments
+/// translates differently, as one row per sequence and position.
+pub(crate) fn write_comparison(
+    first: impl Iterator<Item = std::io::Result<FastaSeq>>, second: impl Iterator<Item = std::io::Result<FastaSeq>>,
+    args: &APDArgs, writer: &mut impl Write, delim: &str, line_ending: &str,
+) -> Result<()> {
+    let translated = |records: Vec<FastaNT>| {
+        records
+            .into_iter()
+            .map(|r| {
+                (
+                    r.name,
+                    translate(r.sequence.as_bytes(), !args.strict_translation, args.partial_codon_policy),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let first = translated(read_all(args, first)?);
+    let second = translated(read_all(args, second).map_err(|e| match e {
+        AadiffError::Input(e) => AadiffError::Invalid(format!("Could not process the other alignment: {e}")),
+        e => e,
+    })?);
+    report_skipped(args);
+
+    writeln!(writer, "sequence{delim}position{delim}first{delim}second{line_ending}").map_err(AadiffError::Output)?;
+
+    // Indexed by name so that each sequence is found in one lookup. A name repeated in
+    // the other alignment is matched to its first record there.
+    let mut by_name = HashMap::with_capacity(second.len());
+    for (name, b) in &second {
+        by_name.entry(name.as_str()).or_insert(b);
+    }
+    let mut unmatched = 0;
+    for (name, a) in &first {
+        let Some(b) = by_name.get(name.as_str()) else {
+            unmatched += 1;
+            continue;
+        };
+
+        let mut field = String::new();
+        push_field(&mut field, name, delim);
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        for i in 0..a.len().max(b.len()) {
+            let x = a.get(i).copied().unwrap_or(b'-');
+            let y = b.get(i).copied().unwrap_or(b'-');
+            if x != y {
+                writeln!(
+                    writer,
+                    "{field}{delim}{}{delim}{}{delim}{}{line_ending}",
+                    i + 1,
+                    x as char,
+                    y as char
+                )
+                .map_err(AadiffError::Output)?;
+            }
+        }
+    }
+
+    let in_first = first.iter().map(|(name, _)| name.as_str()).collect::<HashSet<_>>();
+    unmatched += second.iter().filter(|(name, _)| !in_first.contains(name.as_str())).count();
+    if unmatched > 0 {
+        warn!("{unmatched} sequences are only in one of the alignments and were not compared.");
+    }
+    Ok(())
+}
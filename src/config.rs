@@ -0,0 +1,128 @@
+use crate::{
+    APDArgs,
+    error::{AadiffError, Result},
+};
+use clap::{ArgAction, Command, CommandFactory, parser::ValueSource};
+use std::{ffi::OsString, path::PathBuf};
+
+/// The command line of `aadiff`, with every option also read from an environment
+/// variable named for it, e.g., `AADIFF_OUTPUT_DELIMITER` for `--output-delimiter`.
+pub fn command() -> Command {
+    with_env(APDArgs::command())
+}
+
+fn with_env(command: Command) -> Command {
+    command
+        .mut_args(|arg| match arg.get_long() {
+            // Counted flags, e.g., `-v`, take no value to read from a variable.
+            Some(long)
+                if arg.get_env().is_none()
+                    && !matches!(arg.get_action(), ArgAction::Count | ArgAction::Help | ArgAction::Version) =>
+            {
+                let name = format!("AADIFF_{}", long.to_uppercase().replace('-', "_"));
+                arg.env(name)
+            }
+            _ => arg,
+        })
+        .mut_subcommands(with_env)
+}
+
+/// Adds the options set by the config file named with `--config` or
+/// `AADIFF_CONFIG`, if any, to the command-line arguments, which take precedence
+/// along with environment variables.
+/// Keys are long option names, e.g., `output-delimiter = "\t"`, and options that do
+/// not apply to the subcommand given are skipped.
+pub fn with_config(args: impl IntoIterator<Item = OsString>) -> Result<Vec<OsString>> {
+    let mut args = args.into_iter().collect::<Vec<_>>();
+    let mut root = command();
+    root.build();
+    // Arguments that do not parse are left for the parser to report.
+    let Ok(matches) = root.clone().try_get_matches_from(args.clone()) else {
+        return Ok(args);
+    };
+    let Some(path) = matches.get_one::<PathBuf>("config") else {
+        return Ok(args);
+    };
+
+    let text = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+        kind: "config",
+        path: path.clone(),
+        source,
+    })?;
+    let table = toml::from_str::<toml::Table>(&text)
+        .map_err(|e| AadiffError::Invalid(format!("Config '{}' is not valid TOML: {e}", path.display())))?;
+
+    // Options from the config go right after the subcommand, if any, so that the
+    // command line's own follow them.
+    let (command, matches, at) = match matches.subcommand() {
+        Some((name, sub)) => (
+            root.find_subcommand(name).unwrap_or(&root),
+            sub,
+            args.iter().position(|a| a == name).map_or(1, |i| i + 1),
+        ),
+        None => (&root, &matches, 1),
+    };
+
+    let mut from_config = Vec::new();
+    for (key, value) in table {
+        let Some(arg) = command.get_arguments().find(|a| a.get_long() == Some(key.as_str())) else {
+            if is_option(&root, &key) {
+                continue;
+            }
+            return Err(AadiffError::Invalid(format!(
+                "Config '{}' sets '{key}', which is not an option.",
+                path.display()
+            )));
+        };
+        if matches!(
+            matches.value_source(arg.get_id().as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+
+        let flag = OsString::from(format!("--{key}"));
+        match (arg.get_action(), value) {
+            (ArgAction::SetTrue, toml::Value::Boolean(set)) => {
+                if set {
+                    from_config.push(flag);
+                }
+            }
+            (ArgAction::Count, toml::Value::Integer(n)) => {
+                from_config.extend(std::iter::repeat_n(flag, usize::try_from(n).unwrap_or(0)));
+            }
+            (ArgAction::Append, toml::Value::Array(values)) => {
+                for value in values {
+                    from_config.push(flag.clone());
+                    from_config.push(scalar(&key, value)?);
+                }
+            }
+            (_, value) => {
+                from_config.push(flag);
+                from_config.push(scalar(&key, value)?);
+            }
+        }
+    }
+
+    args.splice(at..at, from_config);
+    Ok(args)
+}
+
+/// Whether `key` is the long name of an option of any subcommand.
+fn is_option(root: &Command, key: &str) -> bool {
+    std::iter::once(root)
+        .chain(root.get_subcommands())
+        .flat_map(Command::get_arguments)
+        .any(|a| a.get_long() == Some(key))
+}
+
+fn scalar(key: &str, value: toml::Value) -> Result<OsString> {
+    match value {
+        toml::Value::String(s) => Ok(s.into()),
+        toml::Value::Integer(n) => Ok(n.to_string().into()),
+        toml::Value::Float(x) => Ok(x.to_string().into()),
+        _ => Err(AadiffError::Invalid(format!(
+            "Config option '{key}' must be a string or a number."
+        ))),
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    APDArgs,
+    data::IUPAC_BY_MASK,
+    error::{AadiffError, Result},
+    records::{read_all, report_skipped},
+    translate::expand_base,
+};
+use std::io::Write;
+use zoe::prelude::*;
+
+/// Tallies the characters seen in each column of an alignment.
+fn column_counts<'a>(sequences: impl IntoIterator<Item = &'a [u8]>) -> Vec<[usize; 256]> {
+    let mut counts: Vec<[usize; 256]> = Vec::new();
+    for sequence in sequences {
+        if counts.len() < sequence.len() {
+            counts.resize(sequence.len(), [0; 256]);
+        }
+        for (column, &base) in counts.iter_mut().zip(sequence) {
+            column[base as usize] += 1;
+        }
+    }
+    counts
+}
+
+/// Builds a majority-rule consensus of aligned nucleotide sequences, one column at a
+/// time. Ties favor canonical bases, then ambiguity codes, then gaps.
+pub(crate) fn majority_consensus<'a>(sequences: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    column_counts(sequences).iter().map(majority_call).collect()
+}
+
+/// Builds a consensus where each column is the IUPAC code for the fewest bases that
+/// together make up at least `threshold` of the column. Ambiguous bases are split
+/// evenly across the bases they represent, and columns that are mostly gaps stay gaps.
+pub(crate) fn threshold_consensus<'a>(sequences: impl IntoIterator<Item = &'a [u8]>, threshold: f64) -> Vec<u8> {
+    column_counts(sequences)
+        .iter()
+        .map(|column| threshold_call(column, threshold))
+        .collect()
+}
+
+fn majority_call(column: &[usize; 256]) -> u8 {
+    (0..=255u8)
+        .filter(|&b| column[b as usize] > 0)
+        .max_by_key(|&b| (column[b as usize], tie_priority(b)))
+        .unwrap_or(b'-')
+}
+
+fn threshold_call(column: &[usize; 256], threshold: f64) -> u8 {
+    if majority_call(column) == b'-' {
+        return b'-';
+    }
+
+    let mut weights = [0.0f64; 4];
+    for b in (0..=255u8).filter(|&b| column[b as usize] > 0) {
+        let bases = expand_base(b);
+        for &base in bases {
+            weights[base_bit(base)] += column[b as usize] as f64 / bases.len() as f64;
+        }
+    }
+
+    let total: f64 = weights.iter().sum();
+    let mut order = [0, 1, 2, 3];
+    order.sort_by(|&a, &b| weights[b].total_cmp(&weights[a]));
+
+    let mut mask = 0;
+    let mut covered = 0.0;
+    for i in order.into_iter().take_while(|&i| weights[i] > 0.0) {
+        mask |= 1 << i;
+        covered += weights[i];
+        if covered >= threshold * total {
+            break;
+        }
+    }
+    IUPAC_BY_MASK[mask]
+}
+
+fn base_bit(base: u8) -> usize {
+    match base {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        _ => 3,
+    }
+}
+
+fn tie_priority(base: u8) -> u8 {
+    match base {
+        b'A' | b'C' | b'G' | b'T' => 2,
+        b'-' => 0,
+        _ => 1,
+    }
+}
+
+/// Writes the consensus of every input record as a single FASTA record.
+pub(crate) fn write_consensus(
+    reader: impl Iterator<Item = std::io::Result<FastaSeq>>, writer: &mut impl Write, threshold: Option<f64>, args: &APDArgs,
+) -> Result<()> {
+    let records = read_all(args, reader)?;
+    report_skipped(args);
+    if records.is_empty() {
+        return Err(AadiffError::Empty("No records available!".to_string()));
+    }
+
+    let sequences = records.iter().map(|r| r.sequence.as_bytes());
+    let consensus = if let Some(threshold) = threshold {
+        threshold_consensus(sequences, threshold)
+    } else {
+        majority_consensus(sequences)
+    };
+    writeln!(writer, ">consensus\n{}", String::from_utf8_lossy(&consensus)).map_err(AadiffError::Output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaks_majority_ties_toward_bases() {
+        let sequences: [&[u8]; 4] = [b"AN-A", b"AN-C", b"N-AG", b"N-AG"];
+        // Ties between a base and an ambiguity code, an ambiguity code and a gap,
+        // and a base and a gap, then a clear majority.
+        assert_eq!(majority_consensus(sequences), b"ANAG");
+        assert_eq!(majority_consensus([b"A-".as_slice(), b"--", b"-G"]), b"--");
+    }
+
+    #[test]
+    fn calls_iupac_codes_by_threshold() {
+        let sequences: [&[u8]; 4] = [b"AR-A", b"AR-A", b"GC-A", b"GCAT"];
+        // A and G split the first column evenly, and R's weight is split between A
+        // and G in the second, leaving C the most common base there.
+        assert_eq!(threshold_consensus(sequences, 0.5), b"AC-A");
+        assert_eq!(threshold_consensus(sequences, 0.75), b"RM-A");
+        assert_eq!(threshold_consensus(sequences, 1.0), b"RV-W");
+    }
+}
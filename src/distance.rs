@@ -0,0 +1,79 @@
+use crate::{
+    APDArgs,
+    align::score,
+    error::{AadiffError, Result},
+    output::push_field,
+    translate::translate,
+};
+use clap::ValueEnum;
+use std::io::Write;
+use zoe::data::fasta::FastaNT;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum DistanceMetric {
+    /// Proportion of compared positions that differ
+    #[default]
+    PDistance,
+    /// Mean BLOSUM62 dissimilarity of compared positions
+    Blosum,
+}
+
+/// Distance between two aligned protein sequences over the positions where both have
+/// a known residue, or `None` when there are no such positions. The BLOSUM62 distance
+/// of a pair of residues is how far their score falls short of the mean of their
+/// self-scores, so identical residues are always 0 apart.
+pub(crate) fn distance(a: &[u8], b: &[u8], metric: DistanceMetric) -> Option<f64> {
+    let mut compared = 0usize;
+    let mut total = 0.0;
+    for (&x, &y) in a.iter().zip(b) {
+        if matches!(x, b'X' | b'-') || matches!(y, b'X' | b'-') {
+            continue;
+        }
+
+        compared += 1;
+        total += match metric {
+            DistanceMetric::PDistance => f64::from(u8::from(x != y)),
+            DistanceMetric::Blosum => f64::from(score(x, x) + score(y, y)) / 2.0 - f64::from(score(x, y)),
+        };
+    }
+
+    (compared > 0).then(|| total / compared as f64)
+}
+
+/// Writes the distance between every pair of sequences, reference first, as a square
+/// matrix. Pairs without any comparable positions are written as `NA`.
+pub(crate) fn write_distances(
+    reference: &FastaNT, queries: &[FastaNT], metric: DistanceMetric, args: &APDArgs, writer: &mut impl Write, delim: &str,
+    line_ending: &str,
+) -> Result<()> {
+    let sequences = std::iter::once(reference)
+        .chain(queries)
+        .map(|s| {
+            (
+                s.name.as_str(),
+                translate(s.sequence.as_bytes(), !args.strict_translation, args.partial_codon_policy),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut buffer = String::new();
+    for (name, _) in &sequences {
+        buffer.push_str(delim);
+        push_field(&mut buffer, name, delim);
+    }
+    writeln!(writer, "{buffer}{line_ending}").map_err(AadiffError::Output)?;
+
+    for (name, a) in &sequences {
+        buffer.clear();
+        push_field(&mut buffer, name, delim);
+        for (_, b) in &sequences {
+            buffer.push_str(delim);
+            match distance(a.as_bytes(), b.as_bytes(), metric) {
+                Some(d) => buffer.push_str(&format!("{d:.4}")),
+                None => buffer.push_str("NA"),
+            }
+        }
+        writeln!(writer, "{buffer}{line_ending}").map_err(AadiffError::Output)?;
+    }
+    Ok(())
+}
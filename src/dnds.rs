@@ -0,0 +1,282 @@
+use crate::{
+    APDArgs, RestrictMode,
+    error::{AadiffError, Result},
+    get_valid_range,
+    output::push_field,
+    translate::{is_canonical, translate, translate_codon},
+};
+use std::io::Write;
+use zoe::data::fasta::FastaNT;
+
+/// Nei–Gojobori site and difference counts accumulated over compared codons.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SiteCounts {
+    pub(crate) nonsynonymous_sites:       f64,
+    pub(crate) synonymous_sites:          f64,
+    pub(crate) nonsynonymous_differences: f64,
+    pub(crate) synonymous_differences:    f64,
+}
+
+impl SiteCounts {
+    /// Counts the codons of `reference` and `query`, both aligned nucleotides, within
+    /// the given codon range. Codon pairs are skipped unless both are unambiguous,
+    /// gap-free, and not stops.
+    pub(crate) fn count(reference: &[u8], query: &[u8], codons: std::ops::Range<usize>) -> Self {
+        let mut counts = SiteCounts::default();
+        for i in codons {
+            let (Some(r), Some(q)) = (reference.get(i * 3..i * 3 + 3), query.get(i * 3..i * 3 + 3)) else {
+                break;
+            };
+            counts.add([r[0], r[1], r[2]], [q[0], q[1], q[2]]);
+        }
+        counts
+    }
+
+    fn add(&mut self, reference: [u8; 3], query: [u8; 3]) {
+        if !is_canonical(reference)
+            || !is_canonical(query)
+            || translate_codon(reference) == b'*'
+            || translate_codon(query) == b'*'
+        {
+            return;
+        }
+
+        let Some((synonymous, nonsynonymous)) = differences(reference, query) else {
+            return;
+        };
+
+        let (ref_synonymous, ref_nonsynonymous) = sites(reference);
+        let (query_synonymous, query_nonsynonymous) = sites(query);
+        self.synonymous_sites += (ref_synonymous + query_synonymous) / 2.0;
+        self.nonsynonymous_sites += (ref_nonsynonymous + query_nonsynonymous) / 2.0;
+        self.synonymous_differences += synonymous;
+        self.nonsynonymous_differences += nonsynonymous;
+    }
+
+    /// Proportion of nonsynonymous sites that differ.
+    pub(crate) fn p_n(&self) -> f64 {
+        self.nonsynonymous_differences / self.nonsynonymous_sites
+    }
+
+    /// Proportion of synonymous sites that differ.
+    pub(crate) fn p_s(&self) -> f64 {
+        self.synonymous_differences / self.synonymous_sites
+    }
+
+    /// Jukes–Cantor corrected nonsynonymous distance.
+    pub(crate) fn d_n(&self) -> f64 {
+        jukes_cantor(self.p_n())
+    }
+
+    /// Jukes–Cantor corrected synonymous distance.
+    pub(crate) fn d_s(&self) -> f64 {
+        jukes_cantor(self.p_s())
+    }
+}
+
+/// Undefined (NaN) when the proportion saturates at or beyond 3/4.
+fn jukes_cantor(p: f64) -> f64 {
+    match p {
+        0.0 => 0.0,
+        p if p < 0.75 => -0.75 * (1.0 - 4.0 * p / 3.0).ln(),
+        _ => f64::NAN,
+    }
+}
+
+/// Synonymous and nonsynonymous sites of a codon, ignoring changes to stop codons.
+fn sites(codon: [u8; 3]) -> (f64, f64) {
+    let aa = translate_codon(codon);
+    let mut synonymous = 0.0;
+    let mut nonsynonymous = 0.0;
+
+    for position in 0..3 {
+        let (mut same, mut total) = (0, 0);
+        for &base in b"ACGT".iter().filter(|&&b| b != codon[position]) {
+            let mut mutant = codon;
+            mutant[position] = base;
+            match translate_codon(mutant) {
+                b'*' => {}
+                m => {
+                    total += 1;
+                    same += usize::from(m == aa);
+                }
+            }
+        }
+
+        if total > 0 {
+            synonymous += same as f64 / total as f64;
+            nonsynonymous += (total - same) as f64 / total as f64;
+        }
+    }
+    (synonymous, nonsynonymous)
+}
+
+/// Synonymous and nonsynonymous differences between two codons, averaged over every
+/// order in which the differing bases could have changed. Pathways through a stop
+/// codon are ignored, and `None` is given when no pathway remains.
+fn differences(reference: [u8; 3], query: [u8; 3]) -> Option<(f64, f64)> {
+    let differing = (0..3).filter(|&p| reference[p] != query[p]).collect::<Vec<_>>();
+    let orders: Vec<Vec<usize>> = match differing.as_slice() {
+        [] => return Some((0.0, 0.0)),
+        &[a] => vec![vec![a]],
+        &[a, b] => vec![vec![a, b], vec![b, a]],
+        _ => vec![
+            vec![0, 1, 2],
+            vec![0, 2, 1],
+            vec![1, 0, 2],
+            vec![1, 2, 0],
+            vec![2, 0, 1],
+            vec![2, 1, 0],
+        ],
+    };
+
+    let mut pathways = 0;
+    let mut synonymous = 0.0;
+    let mut nonsynonymous = 0.0;
+    'pathway: for order in orders {
+        let mut codon = reference;
+        let (mut s, mut n) = (0.0, 0.0);
+        for position in order {
+            let before = translate_codon(codon);
+            codon[position] = query[position];
+            let after = translate_codon(codon);
+            if after == b'*' {
+                continue 'pathway;
+            }
+            if before == after {
+                s += 1.0;
+            } else {
+                n += 1.0;
+            }
+        }
+        pathways += 1;
+        synonymous += s;
+        nonsynonymous += n;
+    }
+
+    (pathways > 0).then(|| (synonymous / pathways as f64, nonsynonymous / pathways as f64))
+}
+
+/// Writes the Nei–Gojobori dN/dS of each query over its comparable codons, and of
+/// each window of codons when asked to. Undefined values are written as `NA`.
+pub(crate) fn write_dnds(
+    reference: &FastaNT, queries: &[FastaNT], window: Option<usize>, args: &APDArgs, writer: &mut impl Write, delim: &str,
+    line_ending: &str,
+) -> Result<()> {
+    let restriction = args.restriction();
+    let translated = |codons: &[u8]| translate(codons, !args.strict_translation, args.partial_codon_policy);
+    let ref_codons = reference.sequence.as_bytes();
+    let ref_range = get_valid_range(translated(ref_codons).iter().copied(), restriction.is_some())?;
+    let mut ranges = queries
+        .iter()
+        .map(|q| {
+            let valid_range = get_valid_range(
+                translated(q.sequence.as_bytes()).iter().copied(),
+                RestrictMode::restricts_queries(restriction),
+            )?;
+            Ok(ref_range.start.max(valid_range.start)..ref_range.end.min(valid_range.end))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    RestrictMode::apply(restriction, &ref_range, ranges.iter_mut());
+    let format = |value: f64| {
+        if value.is_finite() {
+            format!("{value:.4}")
+        } else {
+            "NA".to_string()
+        }
+    };
+
+    writeln!(
+        writer,
+        "sequence{delim}codons{delim}N{delim}S{delim}Nd{delim}Sd{delim}pN{delim}pS{delim}dN{delim}dS{delim}dN/dS{line_ending}"
+    ).map_err(AadiffError::Output)?;
+
+    for (query, range) in queries.iter().zip(ranges) {
+        let query_codons = query.sequence.as_bytes();
+
+        let windows = std::iter::once(range.clone()).chain(
+            window
+                .filter(|&w| w > 0)
+                .into_iter()
+                .flat_map(|w| range.clone().step_by(w).map(move |s| s..(s + w).min(range.end))),
+        );
+
+        let mut name = String::new();
+        push_field(&mut name, &query.name, delim);
+        for codons in windows {
+            if codons.is_empty() {
+                continue;
+            }
+
+            let counts = SiteCounts::count(ref_codons, query_codons, codons.clone());
+            let (d_n, d_s) = (counts.d_n(), counts.d_s());
+            writeln!(
+                writer,
+                "{name}{delim}{start}-{end}{delim}{n}{delim}{s}{delim}{nd}{delim}{sd}{delim}{pn}{delim}{ps}{delim}{dn}{delim}{ds}{delim}{ratio}{line_ending}",
+                start = codons.start + 1,
+                end = codons.end,
+                n = format(counts.nonsynonymous_sites),
+                s = format(counts.synonymous_sites),
+                nd = format(counts.nonsynonymous_differences),
+                sd = format(counts.synonymous_differences),
+                pn = format(counts.p_n()),
+                ps = format(counts.p_s()),
+                dn = format(d_n),
+                ds = format(d_s),
+                ratio = format(if d_s > 0.0 { d_n / d_s } else { f64::NAN }),
+            ).map_err(AadiffError::Output)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close((a, b): (f64, f64), (x, y): (f64, f64)) {
+        assert!((a - x).abs() < 1e-9 && (b - y).abs() < 1e-9, "({a}, {b}) != ({x}, {y})");
+    }
+
+    #[test]
+    fn counts_sites_without_stops() {
+        // TTT: only TTC of the third position's changes keeps F.
+        assert_close(sites(*b"TTT"), (1.0 / 3.0, 8.0 / 3.0));
+        // CTA: every third-position change keeps L, as does TTA at the first.
+        assert_close(sites(*b"CTA"), (4.0 / 3.0, 5.0 / 3.0));
+        // TAT: TAA and TAG are stops, leaving only the synonymous TAC.
+        assert_close(sites(*b"TAT"), (1.0, 2.0));
+        assert_close(sites(*b"TGG"), (0.0, 3.0));
+        assert_close(sites(*b"ATG"), (0.0, 3.0));
+    }
+
+    #[test]
+    fn averages_differences_over_pathways() {
+        assert_close(differences(*b"CTT", *b"CTT").unwrap(), (0.0, 0.0));
+        assert_close(differences(*b"CTT", *b"CTC").unwrap(), (1.0, 0.0));
+        assert_close(differences(*b"CTT", *b"ATT").unwrap(), (0.0, 1.0));
+        // F -> L through either L (CTT) or F (TTC).
+        assert_close(differences(*b"TTT", *b"CTC").unwrap(), (1.0, 1.0));
+        // W -> Y through TAG is dropped, leaving only the pathway through C (TGC).
+        assert_close(differences(*b"TGG", *b"TAC").unwrap(), (0.0, 2.0));
+    }
+
+    #[test]
+    fn counts_only_comparable_codons() {
+        let counts = SiteCounts::count(b"TTTCTTTAACTTATG", b"TTCCTTTAACTNATG", 0..5);
+        // TTT/TTC and CTT/CTT, then ATG/ATG; the stop and the ambiguous codon are skipped.
+        assert_close(
+            (counts.synonymous_sites, counts.nonsynonymous_sites),
+            (1.0 / 3.0 + 1.0, 8.0 / 3.0 + 2.0 + 3.0),
+        );
+        assert_close((counts.synonymous_differences, counts.nonsynonymous_differences), (1.0, 0.0));
+        assert_close((counts.p_s(), counts.p_n()), (0.75, 0.0));
+        // A proportion of 3/4 saturates the Jukes–Cantor correction.
+        assert!(counts.d_s().is_nan());
+        assert_eq!(counts.d_n(), 0.0);
+        assert_close(
+            (jukes_cantor(0.1), jukes_cantor(0.5)),
+            (-0.75 * (1.0 - 0.4 / 3.0_f64).ln(), -0.75 * (1.0 / 3.0_f64).ln()),
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    error::{AadiffError, Result},
+    output::push_field,
+};
+use std::{collections::HashMap, io::Write, path::Path};
+
+/// Reads a two-column file of sequence names and collection dates, separated by a tab
+/// or comma. Dates are ISO 8601 (`2024-03-15`), possibly with the day or month left
+/// off. Blank lines, `#` comments, and lines without a date are skipped.
+pub(crate) fn read_dates(path: &Path) -> Result<HashMap<String, String>> {
+    let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+        kind: "date file",
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    Ok(contents
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, date) = line.split_once('\t').or_else(|| line.split_once(','))?;
+            let date = date.trim();
+            is_iso_date(date).then(|| (name.trim().to_string(), date.to_string()))
+        })
+        .collect())
+}
+
+fn is_iso_date(date: &str) -> bool {
+    let parts = date.split('-').collect::<Vec<_>>();
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    match parts.as_slice() {
+        [year] => digits(year, 4),
+        [year, month] => digits(year, 4) && digits(month, 2),
+        [year, month, day] => digits(year, 4) && digits(month, 2) && digits(day, 2),
+        _ => false,
+    }
+}
+
+/// Tracks when each mutation is seen across dated queries, binned by month.
+pub(crate) struct Emergence {
+    dates:     Vec<Option<String>>,
+    bins:      Vec<String>,
+    mutations: Vec<(String, Vec<usize>)>,
+}
+
+impl Emergence {
+    /// Looks up the date of each query. Queries without a date, or without a month
+    /// for binning, are left out of the parts of the report that need them.
+    pub(crate) fn new<'a>(dates: &HashMap<String, String>, names: impl Iterator<Item = &'a str>) -> Self {
+        let dates = names.map(|name| dates.get(name).cloned()).collect::<Vec<_>>();
+        let mut bins = dates
+            .iter()
+            .flatten()
+            .filter_map(|d| month(d).map(str::to_string))
+            .collect::<Vec<_>>();
+        bins.sort_unstable();
+        bins.dedup();
+
+        Emergence {
+            dates,
+            bins,
+            mutations: Vec::new(),
+        }
+    }
+
+    /// Notes that `query` carries `mutation`, e.g., `K156E`.
+    pub(crate) fn record(&mut self, mutation: String, query: usize) {
+        match self.mutations.iter_mut().find(|(m, _)| *m == mutation) {
+            Some((_, queries)) => queries.push(query),
+            None => self.mutations.push((mutation, vec![query])),
+        }
+    }
+
+    /// Writes one row per mutation with the earliest date it was seen, the number
+    /// of sequences carrying it, and its frequency among the sequences of each month.
+    pub(crate) fn write(&self, writer: &mut impl Write, delim: &str, line_ending: &str) -> std::io::Result<()> {
+        let mut header = format!("mutation{delim}first_seen{delim}count");
+        for bin in &self.bins {
+            header.push_str(delim);
+            header.push_str(bin);
+        }
+        writeln!(writer, "{header}{line_ending}")?;
+
+        let totals = self
+            .bins
+            .iter()
+            .map(|bin| self.dates.iter().flatten().filter(|d| month(d) == Some(bin)).count())
+            .collect::<Vec<_>>();
+
+        for (mutation, queries) in &self.mutations {
+            let dates = queries.iter().filter_map(|&q| self.dates[q].as_deref()).collect::<Vec<_>>();
+            let first_seen = dates.iter().min().copied().unwrap_or("NA");
+            let mut row = String::new();
+            push_field(&mut row, mutation, delim);
+            row.push_str(&format!("{delim}{first_seen}{delim}{}", queries.len()));
+            for (bin, total) in self.bins.iter().zip(&totals) {
+                let carrying = dates.iter().filter(|d| month(d) == Some(bin)).count();
+                row.push_str(delim);
+                row.push_str(&format!("{:.3}", carrying as f64 / *total as f64));
+            }
+            writeln!(writer, "{row}{line_ending}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The `YYYY-MM` month of a date, if it has one.
+fn month(date: &str) -> Option<&str> {
+    date.get(..7).filter(|_| date.len() >= 7)
+}
@@ -0,0 +1,63 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// Everything that can stop a run of `aadiff`.
+#[derive(Debug, Error)]
+pub enum AadiffError {
+    /// An input or output file could not be opened.
+    #[error("Could not open '{}': {source}", path.display())]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A supporting file, e.g., a watchlist, could not be read.
+    #[error("Could not read {kind} '{}': {source}", path.display())]
+    Read {
+        kind: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A line of a supporting file is not valid.
+    #[error("{kind} '{}' line {line}: {message}", path.display())]
+    Parse {
+        kind:    &'static str,
+        path:    PathBuf,
+        line:    usize,
+        message: String,
+    },
+    /// The input sequences could not be read.
+    #[error("Could not process input data: {0}")]
+    Input(#[source] io::Error),
+    /// The output could not be written.
+    #[error("Could not write output: {0}")]
+    Output(#[source] io::Error),
+    /// The input or options cannot be compared as given.
+    #[error("{0}")]
+    Invalid(String),
+    /// The input holds no records to compare.
+    #[error("{0}")]
+    Empty(String),
+    /// No query differs from the reference, with `--exit-code`.
+    #[error("No differences found.")]
+    NoDifferences,
+}
+
+impl AadiffError {
+    /// The status the command line exits with, so that pipelines can tell failures
+    /// apart: 1 for input or options that are not valid, 3 for empty input, 4 for
+    /// files that could not be opened, read, or written, and 5 for no differences.
+    /// Usage errors exit with 2.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AadiffError::Parse { .. } | AadiffError::Invalid(_) => 1,
+            AadiffError::Input(e) if e.kind() == io::ErrorKind::InvalidData => 1,
+            AadiffError::Empty(_) => 3,
+            AadiffError::Open { .. } | AadiffError::Read { .. } | AadiffError::Input(_) | AadiffError::Output(_) => 4,
+            AadiffError::NoDifferences => 5,
+        }
+    }
+}
+
+pub type Result<T, E = AadiffError> = std::result::Result<T, E>;
@@ -0,0 +1,266 @@
+use crate::error::{AadiffError, Result};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+use zoe::prelude::*;
+
+/// One record of a `.fai`-style index: its name, the number of bases, the byte
+/// offset of its first base, and the bases and bytes per full line.
+pub(crate) struct FaiEntry {
+    pub(crate) name: String,
+    length:          usize,
+    offset:          u64,
+    line_bases:      usize,
+    line_width:      usize,
+}
+
+impl FaiEntry {
+    /// The number of bytes from the first base to the last, line breaks included.
+    fn span(&self) -> usize {
+        match self.length.checked_sub(1) {
+            Some(last) => last / self.line_bases * self.line_width + last % self.line_bases + 1,
+            None => 0,
+        }
+    }
+}
+
+fn index_path(fasta: &Path) -> PathBuf {
+    let mut path = fasta.as_os_str().to_owned();
+    path.push(".fai");
+    PathBuf::from(path)
+}
+
+/// Reads the index next to `fasta`, e.g., `alignment.fasta.fai`, building and
+/// saving it first if there is none or it is out of date.
+pub(crate) fn read_or_build(fasta: &Path) -> Result<Vec<FaiEntry>> {
+    let path = index_path(fasta);
+    if path.exists() {
+        let entries = read(&path).map_err(|source| AadiffError::Read {
+            kind: "FASTA index",
+            path: path.clone(),
+            source,
+        })?;
+        if is_current(&path, fasta, &entries) {
+            return Ok(entries);
+        }
+        info!("Rebuilding FASTA index '{}', which is out of date", path.display());
+    }
+
+    let entries = build(fasta).map_err(|source| AadiffError::Read {
+        kind: "input to index",
+        path: fasta.to_path_buf(),
+        source,
+    })?;
+    if let Err(e) = write(&path, &entries) {
+        warn!("Could not save FASTA index '{}': {e}", path.display());
+    }
+    Ok(entries)
+}
+
+/// Whether an index still describes `fasta`: it was written no earlier than the
+/// file was last changed, and every record it lists ends within the file.
+fn is_current(index: &Path, fasta: &Path, entries: &[FaiEntry]) -> bool {
+    let (Ok(index), Ok(fasta)) = (std::fs::metadata(index), std::fs::metadata(fasta)) else {
+        return false;
+    };
+    let written_after = match (index.modified(), fasta.modified()) {
+        (Ok(index), Ok(fasta)) => index >= fasta,
+        _ => false,
+    };
+    written_after && entries.iter().all(|e| e.offset + e.span() as u64 <= fasta.len())
+}
+
+fn read(path: &Path) -> io::Result<Vec<FaiEntry>> {
+    let invalid = |n: usize| io::Error::new(io::ErrorKind::InvalidData, format!("line {} is not a valid entry", n + 1));
+
+    let mut entries = Vec::new();
+    for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let [name, length, offset, line_bases, line_width, ..] = fields.as_slice() else {
+            return Err(invalid(n));
+        };
+        entries.push(FaiEntry {
+            name:       name.to_string(),
+            length:     length.parse().map_err(|_| invalid(n))?,
+            offset:     offset.parse().map_err(|_| invalid(n))?,
+            line_bases: line_bases.parse().map_err(|_| invalid(n))?,
+            line_width: line_width.parse().map_err(|_| invalid(n))?,
+        });
+    }
+    Ok(entries)
+}
+
+fn write(path: &Path, entries: &[FaiEntry]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(File::create(path)?);
+    for e in entries {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}",
+            e.name, e.length, e.offset, e.line_bases, e.line_width
+        )?;
+    }
+    writer.flush()
+}
+
+/// Scans a FASTA file for the layout of each record. Every line of a record but its
+/// last must hold the same number of bases, with no blank lines between them.
+fn build(fasta: &Path) -> io::Result<Vec<FaiEntry>> {
+    let mut reader = BufReader::new(File::open(fasta)?);
+    let mut entries: Vec<FaiEntry> = Vec::new();
+    let mut offset = 0;
+    let mut last_line_short = false;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let width = reader.read_until(b'\n', &mut line)?;
+        if width == 0 {
+            break;
+        }
+        offset += width as u64;
+
+        if let Some(header) = line.strip_prefix(b">") {
+            entries.push(FaiEntry {
+                // As with samtools, records are named by the first word of their header.
+                name: String::from_utf8_lossy(header)
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                length: 0,
+                offset,
+                line_bases: 0,
+                line_width: 0,
+            });
+            last_line_short = false;
+            continue;
+        }
+
+        let bases = line.iter().filter(|b| !b.is_ascii_whitespace()).count();
+        let Some(entry) = entries.last_mut() else {
+            if bases == 0 {
+                continue;
+            }
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "sequence before the first header"));
+        };
+        if bases == 0 {
+            // Blank lines before the bases are skipped over; after them, no more
+            // bases may follow.
+            if entry.line_bases == 0 {
+                entry.offset = offset;
+            } else {
+                last_line_short = true;
+            }
+            continue;
+        }
+
+        if entry.line_bases == 0 {
+            (entry.line_bases, entry.line_width) = (bases, width);
+        } else if last_line_short || bases > entry.line_bases || (bases == entry.line_bases && width != entry.line_width) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("record '{}' has lines of differing lengths", entry.name),
+            ));
+        }
+        last_line_short = bases < entry.line_bases;
+        entry.length += bases;
+    }
+    Ok(entries)
+}
+
+/// Records fetched from an indexed FASTA file by seeking straight to each of them.
+pub(crate) struct IndexedFasta {
+    file:    File,
+    entries: std::vec::IntoIter<FaiEntry>,
+}
+
+impl IndexedFasta {
+    pub(crate) fn open(path: &Path, entries: Vec<FaiEntry>) -> io::Result<Self> {
+        Ok(IndexedFasta {
+            file:    File::open(path)?,
+            entries: entries.into_iter(),
+        })
+    }
+}
+
+impl Iterator for IndexedFasta {
+    type Item = io::Result<FastaSeq>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        let mut bytes = vec![0; entry.span()];
+        let read = self
+            .file
+            .seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| self.file.read_exact(&mut bytes));
+        Some(read.map(|()| {
+            bytes.retain(|b| !b.is_ascii_whitespace());
+            FastaSeq {
+                name:     entry.name,
+                sequence: bytes,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fasta(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("aadiff-faidx-{}-{name}.fasta", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn indexes_wrapped_crlf_records() {
+        let path = fasta(
+            "crlf",
+            ">r1 first record\r\nACGTA\r\nCGTAC\r\nGT\r\n\r\n>r2\r\n\r\nAAAAA\r\nCC\r\n",
+        );
+        let entries = build(&path).unwrap();
+        let layout = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.length, e.offset, e.line_bases, e.line_width))
+            .collect::<Vec<_>>();
+        // The blank line after r2's header moves its first base along.
+        assert_eq!(layout, [("r1", 12, 18, 5, 7), ("r2", 7, 45, 5, 7)]);
+        assert_eq!(entries[0].span(), 16);
+
+        write(&index_path(&path), &entries).unwrap();
+        let entries = read(&index_path(&path)).unwrap();
+        let records = IndexedFasta::open(&path, entries)
+            .unwrap()
+            .map(|r| r.map(|r| (r.name, r.sequence)))
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            records,
+            [
+                ("r1".to_string(), b"ACGTACGTACGT".to_vec()),
+                ("r2".to_string(), b"AAAAACC".to_vec())
+            ]
+        );
+        std::fs::remove_file(index_path(&path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn refuses_ragged_lines() {
+        for (name, contents) in [
+            ("longer", ">r\nACG\nACGTA\n"),
+            ("after-short", ">r\nACGT\nAC\nACGT\n"),
+            ("after-blank", ">r\nACGT\n\nACGT\n"),
+            ("mixed-endings", ">r\nACGT\r\nACGT\nAC\n"),
+        ] {
+            let path = fasta(name, contents);
+            assert!(build(&path).is_err(), "{name}");
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::{self, BufRead};
+use zoe::prelude::*;
+
+/// FASTA records read line by line from a stream. Windows line endings, trailing
+/// whitespace, and blank lines between or within records are tolerated, and a
+/// record that cannot be parsed is reported by its line number, after which reading
+/// resumes at the next header.
+pub(crate) struct FastaLines<R: BufRead> {
+    reader: R,
+    line:   Vec<u8>,
+    /// The number of lines read so far
+    number: usize,
+    /// The name in a header read while finishing the record before it
+    header: Option<String>,
+    done:   bool,
+}
+
+impl<R: BufRead> FastaLines<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        FastaLines {
+            reader,
+            line:   Vec::new(),
+            number: 0,
+            header: None,
+            done:   false,
+        }
+    }
+
+    /// Reads the next line, without its line ending or trailing whitespace, or `None`
+    /// at the end of the input.
+    fn next_line(&mut self) -> io::Result<Option<&[u8]>> {
+        self.line.clear();
+        if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(None);
+        }
+        self.number += 1;
+        Ok(Some(self.line.trim_ascii_end()))
+    }
+
+    /// Skips lines up to the next header, keeping it for the next record.
+    fn skip_to_header(&mut self) -> io::Result<()> {
+        while let Some(line) = self.next_line()? {
+            if let Some(name) = line.strip_prefix(b">") {
+                self.header = Some(String::from_utf8_lossy(name.trim_ascii()).into_owned());
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<FastaSeq>> {
+        let name = match self.header.take() {
+            Some(name) => name,
+            None => loop {
+                let Some(line) = self.next_line()? else {
+                    return Ok(None);
+                };
+                if line.is_empty() {
+                    continue;
+                }
+                match line.strip_prefix(b">") {
+                    Some(name) => break String::from_utf8_lossy(name.trim_ascii()).into_owned(),
+                    None => {
+                        let number = self.number;
+                        self.skip_to_header()?;
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("line {number}: expected a FASTA header starting with '>'"),
+                        ));
+                    }
+                }
+            },
+        };
+
+        let mut sequence = Vec::new();
+        while let Some(line) = self.next_line()? {
+            if let Some(next) = line.strip_prefix(b">") {
+                self.header = Some(String::from_utf8_lossy(next.trim_ascii()).into_owned());
+                break;
+            }
+            sequence.extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+        }
+        Ok(Some(FastaSeq { name, sequence }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastaLines<R> {
+    type Item = io::Result<FastaSeq>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Some(Err(e)),
+            // The stream itself failed, so nothing more can be read from it.
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
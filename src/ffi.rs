@@ -0,0 +1,214 @@
+use crate::{
+    APDArgs, DiffIter,
+    error::{AadiffError, Result},
+    table::CellValue,
+};
+use clap::Parser;
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int, c_void},
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr,
+};
+
+/// Called once with the column names, reference first and followed by any extra
+/// columns.
+pub type AadiffHeaderCallback = extern "C" fn(columns: *const *const c_char, n_columns: usize, user_data: *mut c_void);
+
+/// Called once per row of the difference table. Returning nonzero stops the run:
+/// no further row is passed, and the run has ended, writing nothing more, by the
+/// time [`aadiff_run`] returns.
+pub type AadiffRowCallback = extern "C" fn(row: *const AadiffRow, user_data: *mut c_void) -> c_int;
+
+/// The options of a run, given as they are written on the command line but without
+/// the program name, e.g., `{"-i", "alignment.fasta", "-r"}`.
+#[repr(C)]
+pub struct AadiffConfig {
+    pub args:   *const *const c_char,
+    pub n_args: usize,
+    /// May be null.
+    pub header: Option<AadiffHeaderCallback>,
+    /// Passed through to the callbacks untouched.
+    pub user_data: *mut c_void,
+}
+
+/// A row of the difference table, valid only for the duration of the callback.
+/// Empty cells are null.
+#[repr(C)]
+pub struct AadiffRow {
+    pub label:     *const c_char,
+    pub reference: *const c_char,
+    pub cells:     *const *const c_char,
+    pub n_cells:   usize,
+    pub extras:    *const *const c_char,
+    pub n_extras:  usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `aadiff` with `config`, passing each row of the difference table to
+/// `callback` instead of writing it. Returns 0 on success, 1 if the run failed, in
+/// which case [`aadiff_last_error`] describes why, and 2 if `callback` stopped it.
+/// A null `config` or `callback` fails the run. Progress bars are never drawn.
+///
+/// # Safety
+///
+/// `config` must be null or point to a valid [`AadiffConfig`] whose `args` holds
+/// `n_args` NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aadiff_run(config: *const AadiffConfig, callback: Option<AadiffRowCallback>) -> c_int {
+    let Some(config) = (unsafe { config.as_ref() }) else {
+        set_last_error("No configuration was given.");
+        return 1;
+    };
+    let Some(callback) = callback else {
+        set_last_error("No row callback was given.");
+        return 1;
+    };
+
+    let args = if config.n_args == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(config.args, config.n_args) }
+    };
+    let args = std::iter::once("aadiff".to_string()).chain(
+        args.iter()
+            .map(|&arg| unsafe { CStr::from_ptr(arg) }.to_string_lossy().into_owned()),
+    );
+
+    match catch_unwind(AssertUnwindSafe(|| run(args, config, callback))) {
+        Ok(Ok(true)) => 0,
+        Ok(Ok(false)) => 2,
+        Ok(Err(e)) => {
+            set_last_error(&e.to_string());
+            1
+        }
+        Err(_) => {
+            set_last_error("aadiff panicked.");
+            1
+        }
+    }
+}
+
+/// The reason the last failed [`aadiff_run`] on this thread failed, or null. Valid
+/// until the next run on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn aadiff_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|e| e.as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Gives whether every row was passed to `callback`.
+fn run(args: impl Iterator<Item = String>, config: &AadiffConfig, callback: AadiffRowCallback) -> Result<bool> {
+    LAST_ERROR.with_borrow_mut(|e| *e = None);
+    let mut args = APDArgs::try_parse_from(args).map_err(|e| AadiffError::Invalid(e.to_string()))?;
+    // Bars would draw over the host's own use of stderr, and config files are only
+    // applied by the command line.
+    args.no_progress = true;
+    args.config = None;
+    let rows = DiffIter::new(args)?;
+
+    if let Some(header) = config.header {
+        let columns = rows
+            .columns()
+            .iter()
+            .chain(rows.extras())
+            .map(|c| c_string(c))
+            .collect::<Vec<_>>();
+        let pointers = columns.iter().map(|c| c.as_ptr()).collect::<Vec<_>>();
+        header(pointers.as_ptr(), pointers.len(), config.user_data);
+    }
+
+    for row in rows {
+        let row = row?;
+        let label = c_string(&row.label);
+        let reference = c_cell(&row.reference);
+        let cells = row.cells.iter().map(c_cell).collect::<Vec<_>>();
+        let extras = row.extras.iter().map(c_cell).collect::<Vec<_>>();
+        let cell_pointers = cells.iter().map(cell_ptr).collect::<Vec<_>>();
+        let extra_pointers = extras.iter().map(cell_ptr).collect::<Vec<_>>();
+
+        let row = AadiffRow {
+            label:     label.as_ptr(),
+            reference: cell_ptr(&reference),
+            cells:     cell_pointers.as_ptr(),
+            n_cells:   cell_pointers.len(),
+            extras:    extra_pointers.as_ptr(),
+            n_extras:  extra_pointers.len(),
+        };
+        if callback(&row, config.user_data) != 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn set_last_error(message: &str) {
+    LAST_ERROR.with_borrow_mut(|e| *e = Some(c_string(message)));
+}
+
+/// Drops any interior NULs, which C strings cannot hold.
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).unwrap_or_default()
+}
+
+fn c_cell(cell: &CellValue) -> Option<CString> {
+    cell.as_str().map(c_string)
+}
+
+fn cell_ptr(cell: &Option<CString>) -> *const c_char {
+    cell.as_ref().map_or(ptr::null(), |c| c.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    extern "C" fn stop_after_first(_: *const AadiffRow, user_data: *mut c_void) -> c_int {
+        let rows = unsafe { &mut *user_data.cast::<usize>() };
+        *rows += 1;
+        1
+    }
+
+    #[test]
+    fn stops_when_the_callback_returns_nonzero() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("aadiff-ffi-{}.fasta", std::process::id()));
+        let output = dir.join(format!("aadiff-ffi-{}.csv", std::process::id()));
+        let codons = 1000;
+        std::fs::write(
+            &input,
+            format!(">ref\n{}\n>q1\n{}\n", "ATG".repeat(codons), "ATA".repeat(codons)),
+        )
+        .unwrap();
+
+        let args = [
+            c_string("-i"),
+            c_string(&input.to_string_lossy()),
+            c_string("--output-csv"),
+            c_string(&output.to_string_lossy()),
+        ];
+        let pointers = args.iter().map(|a| a.as_ptr()).collect::<Vec<_>>();
+        let mut rows = 0usize;
+        let config = AadiffConfig {
+            args:      pointers.as_ptr(),
+            n_args:    pointers.len(),
+            header:    None,
+            user_data: (&raw mut rows).cast(),
+        };
+        assert_eq!(unsafe { aadiff_run(&config, Some(stop_after_first)) }, 2);
+        assert_eq!(rows, 1);
+
+        // The run has ended, so its other output no longer grows.
+        let written = std::fs::metadata(&output).unwrap().len();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(std::fs::metadata(&output).unwrap().len(), written);
+        let table = std::fs::read_to_string(&output).unwrap();
+        assert!(table.lines().count() < codons);
+
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+/// Finds the codon ranges of `query` that are out of frame relative to `reference`.
+///
+/// Both sequences are aligned nucleotides. Internal query gaps (deletions) and
+/// reference gaps (insertions) whose net length is not a multiple of three shift
+/// the reading frame; the shifted region lasts until a later indel restores it
+/// or the sequence ends. Leading and trailing query gaps are treated as missing
+/// data rather than indels.
+pub(crate) fn find_frameshifts(reference: &[u8], query: &[u8]) -> Vec<Range<usize>> {
+    let mut frameshifts = Vec::new();
+
+    let (Some(first), Some(last)) = (query.iter().position(|&b| b != b'-'), query.iter().rposition(|&b| b != b'-')) else {
+        return frameshifts;
+    };
+
+    let mut shift = 0usize;
+    let mut start = None;
+    let codons = reference.len().min(query.len()) / 3;
+    for codon in first / 3..codons {
+        for j in (codon * 3..codon * 3 + 3).filter(|j| (first..=last).contains(j)) {
+            match (reference[j] == b'-', query[j] == b'-') {
+                (false, true) => shift = (shift + 1) % 3,
+                (true, false) => shift = (shift + 2) % 3,
+                _ => {}
+            }
+        }
+
+        match (shift, start) {
+            (0, Some(s)) => {
+                frameshifts.push(s..codon);
+                start = None;
+            }
+            (1 | 2, None) => start = Some(codon),
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start {
+        frameshifts.push(s..codons);
+    }
+
+    frameshifts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFERENCE: &[u8] = b"ATGAAACCCGGGTTT";
+
+    /// The shifted codon ranges, as pairs of their ends.
+    fn shifts(reference: &[u8], query: &[u8]) -> Vec<(usize, usize)> {
+        find_frameshifts(reference, query)
+            .into_iter()
+            .map(|r| (r.start, r.end))
+            .collect()
+    }
+
+    #[test]
+    fn reads_end_gaps_as_missing() {
+        assert!(find_frameshifts(REFERENCE, REFERENCE).is_empty());
+        assert!(find_frameshifts(REFERENCE, b"--GAAACCCGGGT--").is_empty());
+        assert!(find_frameshifts(REFERENCE, b"---------------").is_empty());
+    }
+
+    #[test]
+    fn finds_shifts_at_the_ends() {
+        // In the first codon, and in the last, lasting to the end.
+        assert_eq!(shifts(REFERENCE, b"A-GAAACCCGGGTTT"), [(0, 5)]);
+        assert_eq!(shifts(REFERENCE, b"ATGAAACCCGGGT-T"), [(4, 5)]);
+        // Trailing gaps after a shift do not restore the frame.
+        assert_eq!(shifts(REFERENCE, b"ATGA-ACCC------"), [(1, 5)]);
+        // Nor do leading gaps before one count toward it.
+        assert_eq!(shifts(REFERENCE, b"--GA-ACCCGGGTTT"), [(1, 5)]);
+    }
+
+    #[test]
+    fn ends_shifts_where_the_frame_is_restored() {
+        assert_eq!(shifts(REFERENCE, b"ATGA-ACC--GGTTT"), [(1, 3)]);
+        // An insertion of one base, then a deletion of one.
+        assert_eq!(shifts(b"ATG-AAACCCGGG", b"ATGTAAA-CCGGG"), [(1, 2)]);
+        // Whole-codon indels keep the frame.
+        assert!(find_frameshifts(REFERENCE, b"ATG---CCCGGGTTT").is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+use std::ops::Range;
+
+/// Finds the alignment indices of every N-linked glycosylation sequon, N-X-S/T where
+/// X is not P, reading through alignment gaps.
+pub(crate) fn find_sequons(residues: &[u8]) -> Vec<usize> {
+    let ungapped = residues.iter().enumerate().filter(|&(_, &aa)| aa != b'-').collect::<Vec<_>>();
+
+    ungapped
+        .windows(3)
+        .filter(|w| *w[0].1 == b'N' && !matches!(*w[1].1, b'P' | b'X' | b'*') && matches!(*w[2].1, b'S' | b'T'))
+        .map(|w| w[0].0)
+        .collect()
+}
+
+/// Compares the sequons of a query to those of the reference within the query's
+/// comparable range, giving the indices of the sites gained and those lost.
+pub(crate) fn sequon_changes(reference: &[usize], query: &[usize], valid_range: &Range<usize>) -> (Vec<usize>, Vec<usize>) {
+    let gained = query
+        .iter()
+        .copied()
+        .filter(|i| valid_range.contains(i) && !reference.contains(i))
+        .collect();
+    let lost = reference
+        .iter()
+        .copied()
+        .filter(|i| valid_range.contains(i) && !query.contains(i))
+        .collect();
+    (gained, lost)
+}
@@ -0,0 +1,46 @@
+use std::ops::Range;
+use zoe::prelude::*;
+
+/// Numbers each reference codon by its 1-based position in the ungapped reference.
+/// Gap codons share the number of the residue preceding them.
+pub(crate) fn ungapped_positions(reference: &AminoAcids) -> Vec<usize> {
+    reference
+        .iter()
+        .scan(0, |n, &aa| {
+            if aa != b'-' {
+                *n += 1;
+            }
+            Some(*n)
+        })
+        .collect()
+}
+
+/// Collects the query residues aligned to reference gap codons, grouped by the
+/// alignment index of the reference residue they follow (`None` before the first).
+pub(crate) fn find_insertions(
+    reference: &AminoAcids, query: &AminoAcids, valid_range: &Range<usize>,
+) -> Vec<(Option<usize>, String)> {
+    let mut insertions: Vec<(Option<usize>, String)> = Vec::new();
+    let mut anchor = None;
+
+    for (i, (&r, &q)) in reference.iter().zip(query.iter()).enumerate() {
+        if r != b'-' {
+            anchor = Some(i);
+            continue;
+        }
+
+        if !valid_range.contains(&i) || q == b'-' {
+            continue;
+        }
+
+        if let Some((a, residues)) = insertions.last_mut()
+            && *a == anchor
+        {
+            residues.push(q as char);
+        } else {
+            insertions.push((anchor, String::from(q as char)));
+        }
+    }
+
+    insertions
+}
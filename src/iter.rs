@@ -0,0 +1,116 @@
+use crate::{
+    APDArgs, Io,
+    error::{AadiffError, Result},
+    output::TableEvent,
+    table::PositionDiff,
+};
+use std::{
+    sync::mpsc::{Receiver, sync_channel},
+    thread::JoinHandle,
+};
+
+/// The number of rows computed ahead of those consumed.
+const ROW_BUFFER: usize = 64;
+
+/// The rows of the difference table, computed on another thread and yielded one
+/// [`PositionDiff`] at a time, only a few ahead of the consumer. Dropping the
+/// iterator early stops the run at its next row and waits for it to end; any other
+/// outputs named by the arguments, e.g., `--output-json`, are only complete once it
+/// has run to the end.
+pub struct DiffIter {
+    columns: Vec<String>,
+    extras:  Vec<String>,
+    rows:    Receiver<TableEvent>,
+    worker:  Option<JoinHandle<Result<()>>>,
+}
+
+impl DiffIter {
+    /// Starts computing the difference table for `args`, waiting for its column
+    /// names. Subcommands, which write other tables, cannot be iterated over.
+    pub fn new(args: APDArgs) -> Result<Self> {
+        if !args.writes_table() {
+            return Err(AadiffError::Invalid(
+                "Only the difference table can be iterated over.".to_string(),
+            ));
+        }
+
+        let (sender, rows) = sync_channel(ROW_BUFFER);
+        let worker = std::thread::spawn(move || crate::diff(args, Io::Channel(sender)));
+        let mut iter = DiffIter {
+            columns: Vec::new(),
+            extras:  Vec::new(),
+            rows,
+            worker: Some(worker),
+        };
+        match iter.rows.recv() {
+            Ok(TableEvent::Header { columns, extras }) => (iter.columns, iter.extras) = (columns, extras),
+            Ok(TableEvent::Row(_)) => unreachable!("rows are sent after the header"),
+            Err(_) => iter.finish()?,
+        }
+        Ok(iter)
+    }
+
+    /// The sequence column names, reference first.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The names of any extra annotation columns.
+    pub fn extras(&self) -> &[String] {
+        &self.extras
+    }
+
+    /// Waits for the worker, giving the error that stopped it, if any.
+    fn finish(&mut self) -> Result<()> {
+        match self.worker.take() {
+            Some(worker) => worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for DiffIter {
+    fn drop(&mut self) {
+        // Hanging up first fails the worker's next send, so that it stops rather than
+        // computing rows no one will read. The error it then stops with is expected.
+        let (_, hung_up) = sync_channel(0);
+        drop(std::mem::replace(&mut self.rows, hung_up));
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Iterator for DiffIter {
+    type Item = Result<PositionDiff>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.rows.recv() {
+                Ok(TableEvent::Row(row)) => return Some(Ok(row)),
+                Ok(TableEvent::Header { columns, extras }) => (self.columns, self.extras) = (columns, extras),
+                Err(_) => return self.finish().err().map(Err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DiffOptions;
+
+    #[test]
+    fn stops_when_dropped_early() {
+        // Far more differing codons than rows are buffered ahead of the consumer.
+        let codons = 1000;
+        let path = std::env::temp_dir().join(format!("aadiff-iter-{}.fasta", std::process::id()));
+        let fasta = format!(">ref\n{}\n>q1\n{}\n", "ATG".repeat(codons), "ATA".repeat(codons));
+        std::fs::write(&path, fasta).unwrap();
+
+        let mut rows = DiffOptions::new().input(&path).iter().unwrap();
+        let first = rows.next().unwrap().unwrap();
+        assert_eq!(first.label, "1");
+        drop(rows);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,1638 @@
+#![cfg_attr(feature = "nightly", feature(portable_simd))]
+#![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
+
+pub(crate) mod align;
+pub(crate) mod annotation;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod batch;
+pub(crate) mod cells;
+pub(crate) mod columns;
+pub(crate) mod compare;
+pub mod config;
+pub(crate) mod consensus;
+pub(crate) mod data;
+pub(crate) mod distance;
+pub(crate) mod dnds;
+pub(crate) mod emergence;
+pub mod error;
+pub(crate) mod faidx;
+pub(crate) mod fasta;
+pub mod ffi;
+pub(crate) mod frameshift;
+pub(crate) mod glycosylation;
+pub(crate) mod insertions;
+pub mod iter;
+pub(crate) mod mmap;
+pub(crate) mod names;
+pub(crate) mod numbering;
+pub mod options;
+pub mod output;
+pub(crate) mod progress;
+pub(crate) mod properties;
+pub(crate) mod provenance;
+pub(crate) mod qc;
+pub(crate) mod queries;
+pub(crate) mod records;
+pub(crate) mod scan;
+pub(crate) mod segments;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod serve;
+pub(crate) mod sparse;
+pub(crate) mod stats;
+pub(crate) mod summary;
+pub mod table;
+pub mod translate;
+pub(crate) mod validate;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod view;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+use crate::{
+    annotation::{Groups, Regions, Watchlist},
+    cells::{extra_cells, substitution_notes},
+    columns::Columns,
+    distance::DistanceMetric,
+    emergence::{Emergence, read_dates},
+    error::{AadiffError, Result},
+    fasta::FastaLines,
+    names::{UniqueNames, format_name, parse_name_format, sanitize_name},
+    numbering::{Positions, parse_position_range, read_position_ranges},
+    output::{Custom, JsonSchema, SkipHeader, TableEvent, TableWriter, read_header},
+    progress::Progress,
+    provenance::Provenance,
+    queries::{Comparison, Site, Tally, Translator},
+    records::{
+        check_reference, drop_failing_qc, drop_malformed, read_sequences, recode, recover, report_skipped, trim_to,
+        whole_codons,
+    },
+    sparse::{Baseline, QuerySequence, store_by_column, variable_codons},
+    stats::{Diversity, write_profile_header, write_profile_row},
+    summary::Summary,
+    table::PositionDiff,
+    translate::{PartialCodonPolicy, Recoding, ambiguity_code, is_partially_gapped, translate},
+};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use either::Either;
+pub use iter::DiffIter;
+pub use options::DiffOptions;
+pub use output::{Format, OutputFormat};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{BufReader, BufWriter, IsTerminal, Write, stdout},
+    num::NonZeroUsize,
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock, atomic::AtomicUsize, mpsc::SyncSender},
+    time::Instant,
+};
+use tracing::{Level, info, warn};
+use zoe::data::fasta::FastaAA;
+
+/// The number of queries read and translated at a time in low-memory mode, unless
+/// sized by `--max-memory`.
+const STREAM_CHUNK: usize = 1024;
+
+#[derive(Debug, Parser, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[command(
+    about = "Tool for calculating amino acid difference tables",
+    args_conflicts_with_subcommands = true
+)]
+pub struct APDArgs {
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+
+    #[arg(short = 'i', long, global = true)]
+    /// Optional input fasta; stdin if unset or `-`
+    input_fasta: Option<PathBuf>,
+
+    #[arg(long, global = true, env = "AADIFF_CONFIG")]
+    /// TOML file of default option values, keyed by long option name
+    config: Option<PathBuf>,
+
+    #[arg(short = 'v', long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    /// Log progress and timings on stderr; -vv also logs every sequence
+    verbose: u8,
+
+    #[arg(short = 'q', long, global = true)]
+    /// Only log errors, not warnings such as dropped sequences
+    quiet: bool,
+
+    #[arg(long, global = true)]
+    /// Never show progress bars, which are otherwise shown on stderr when it is a terminal
+    no_progress: bool,
+
+    #[arg(long, global = true, requires = "input_fasta")]
+    /// Memory-map the input file instead of reading it through a buffer
+    mmap: bool,
+
+    #[arg(long, global = true)]
+    /// File of query names to compare, one per line; the first record is always kept
+    include: Option<PathBuf>,
+
+    #[arg(long, global = true, requires_all = ["input_fasta", "include"], conflicts_with = "mmap")]
+    /// Fetch the included records through a .fai index of the input, building it if missing
+    fai: bool,
+
+    #[arg(long, global = true, requires = "input_fasta")]
+    /// Rerun whenever the input file changes, replacing the output each time, until interrupted
+    watch: bool,
+
+    #[arg(long, global = true)]
+    /// Only check the input, writing one line per problem found instead of any table
+    validate: bool,
+
+    #[arg(short = 'o', long, global = true)]
+    /// Optional output delimited file
+    output_xsv: Option<PathBuf>,
+
+    #[arg(long, global = true, requires = "output_xsv", conflicts_with = "append")]
+    /// Overwrite the output file if it exists
+    force: bool,
+
+    #[arg(long, global = true, requires = "output_xsv")]
+    /// Add rows to the end of the output file, without repeating its header
+    append: bool,
+
+    #[arg(short = 'r', long, global = true)]
+    /// Restrict to non-ambiguous alignable regions, pairwise.
+    restrict_to_pairwise_alignable: bool,
+
+    #[arg(long, value_enum, global = true)]
+    /// Restrict as -r does, to each query's own alignable region, to the region alignable in every sequence, or to the reference's
+    restrict: Option<RestrictMode>,
+
+    #[arg(short = 'e', long, global = true)]
+    /// Use unix line-endings instead of Windows ones
+    unix_line_endings: bool,
+
+    #[arg(short = 'd', long, global = true, value_parser = parse_delimiter)]
+    /// Use the provider delimiter for separating fields: any string, `tab`, or one with `\t` escapes. Default is ','
+    output_delimiter: Option<String>,
+
+    #[arg(long, value_enum, default_value_t, global = true)]
+    /// Sequence to diff against: the first record or a majority-rule consensus of all records
+    reference: ReferenceMode,
+
+    #[arg(short = 'a', long, global = true)]
+    /// Codon-align unaligned coding sequences to the reference before comparing them
+    align: bool,
+
+    #[arg(long, global = true)]
+    /// Translate every codon with an ambiguous base as X, even when all its expansions encode the same amino acid
+    strict_translation: bool,
+
+    #[arg(long, global = true)]
+    /// Treat codons with soft-masked (lowercase) bases as ambiguous instead of reading them as uppercase
+    respect_softmask: bool,
+
+    #[arg(long, default_value = ".~?", global = true)]
+    /// Characters read as gaps, such as the periods some aligners use for terminal gaps, or none given as ""
+    gap_chars: String,
+
+    #[arg(long, global = true)]
+    /// Read the input as aligned amino acids rather than nucleotides; B, Z, and J match either residue they stand for
+    protein: bool,
+
+    #[arg(long, global = true)]
+    /// Number of threads used to process query sequences [default: all available cores]
+    threads: Option<usize>,
+
+    #[arg(long, conflicts_with_all = ["align", "segments", "segment_map"], global = true)]
+    /// Read queries in chunks and keep only the codons differing from the reference, for alignments too large for memory
+    low_memory: bool,
+
+    #[arg(long, value_parser = parse_size, conflicts_with = "low_memory", global = true)]
+    /// Memory budget, e.g., 8G, for choosing between holding queries in full and low-memory mode, and for sizing its chunks
+    max_memory: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t, global = true)]
+    /// Translate codons with one or two gaps as deletions, as X, or as X that is never reported
+    partial_codon_policy: PartialCodonPolicy,
+
+    #[arg(long, value_enum, default_value_t, global = true)]
+    /// Fail on records named the same as an earlier one, suffix their names, e.g., name_2, or drop them
+    on_duplicate: DuplicatePolicy,
+
+    #[arg(long, global = true)]
+    /// Fail instead of warning when the reference has ambiguous codons, gaps, or a premature stop
+    strict: bool,
+
+    #[arg(long, global = true)]
+    /// Skip records that cannot be parsed or whose length differs from the reference, with a warning, instead of failing
+    skip_malformed: bool,
+
+    #[arg(skip)]
+    #[serde(skip)]
+    /// The records skipped under `--skip-malformed` so far
+    skipped: AtomicUsize,
+
+    #[arg(skip)]
+    #[serde(skip)]
+    /// Whether an earlier run under `--watch` wrote the outputs, which are replaced
+    rerun: bool,
+
+    #[arg(long, global = true)]
+    /// Ignore the last one or two bases of an alignment that is not a whole number of codons, with a warning, instead of failing
+    trim_partial_codon: bool,
+
+    #[arg(long, value_parser = parse_fraction, global = true)]
+    /// Drop sequences with more than this fraction of ambiguous bases, with a warning
+    max_ambiguity: Option<f64>,
+
+    #[arg(long, value_parser = parse_fraction, global = true)]
+    /// Drop sequences with more than this fraction of gaps, with a warning
+    max_gaps: Option<f64>,
+
+    #[arg(long, global = true)]
+    /// Diff each segment, named after the last `|` of each header, against its own reference
+    segments: bool,
+
+    #[arg(long, global = true)]
+    /// File of record names, segments, and optionally sample names for multi-segment mode
+    segment_map: Option<PathBuf>,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    diff: DiffArgs,
+}
+
+/// The options of the difference table, given either without a subcommand or after
+/// `diff`.
+#[derive(Debug, Args, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffArgs {
+    #[arg(short = 'f', long, value_enum, default_value_t)]
+    /// Format of the difference table
+    output_format: Format,
+
+    #[arg(long)]
+    /// Write the difference table as delimited text to this file, in the same pass as any other output
+    output_csv: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Write the difference table as JSON to this file, in the same pass as any other output
+    output_json: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Write a summary of the differences of each query to this file, in the same pass as any other output
+    output_summary: Option<PathBuf>,
+
+    #[arg(skip)]
+    #[serde(skip)]
+    /// A formatter supplied through the library in place of `output_format`
+    formatter: Option<Custom>,
+
+    #[arg(long, value_enum, default_value_t)]
+    /// Schema of JSON output: v2 wraps the rows in a versioned envelope, v1 is the bare object of rows, records an array of them
+    json_schema: JsonSchema,
+
+    #[arg(long)]
+    /// Embed the aadiff version and the parameters of the run in JSON output (schema v2)
+    json_metadata: bool,
+
+    #[arg(long)]
+    /// Embed the aadiff version, full command line, input checksums, and start time as comment lines (CSV) or metadata (JSON)
+    with_provenance: bool,
+
+    #[arg(skip)]
+    #[serde(skip)]
+    /// The provenance of the run, worked out once for every output
+    provenance: OnceLock<Provenance>,
+
+    #[arg(long, visible_aliases = ["fail-if-no-differences", "fail-if-identical"])]
+    /// Exit with status 5 if no query differs from the reference at any position, or 3 if there are no queries
+    exit_code: bool,
+
+    #[arg(long)]
+    /// When writing the table to a terminal, align its columns and color query cells: substitutions red, deletions blue, ambiguities yellow, masked and frameshifted codons dim
+    color: bool,
+
+    #[arg(long, value_parser = parse_name_format)]
+    /// Template for the sequence column names, e.g., `{field:2}`: {name} is the whole FASTA header, {id} its first word, and {field:N} its Nth field split on '|'
+    name_format: Option<String>,
+
+    #[arg(long)]
+    /// Replace tabs, newlines, and other characters of sequence column names that are not printable ASCII with '_'
+    sanitize_names: bool,
+
+    #[arg(short = 'I', long)]
+    /// Report reference-gap columns as insertions and number positions by the ungapped reference
+    insertions: bool,
+
+    #[arg(long)]
+    /// Report degenerate codons encoding more than this many amino acids as a count, e.g., X(5)
+    degenerate_cap: Option<usize>,
+
+    #[arg(long, conflicts_with = "degenerate_cap")]
+    /// List every amino acid a degenerate codon may encode with its probability, assuming equally likely bases, e.g., I(2/3)/L(1/3)
+    degenerate_probabilities: bool,
+
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "first_position")]
+    /// Add this to every reported position, e.g., to number from the mature protein
+    position_offset: Option<isize>,
+
+    #[arg(long, allow_hyphen_values = true)]
+    /// Number the first codon as this position instead of 1
+    first_position: Option<isize>,
+
+    #[arg(short = 'n', long)]
+    /// Two-column file mapping alignment positions to labels in another numbering scheme
+    numbering: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t)]
+    /// Number positions by alignment column, or by the ungapped reference, leaving out reference-gap columns
+    number_by: NumberBy,
+
+    #[arg(
+        short = 'p',
+        long,
+        value_delimiter = ',',
+        value_parser = parse_position_range,
+        allow_hyphen_values = true
+    )]
+    /// Only report these positions or ranges of positions, e.g., 100-260,300,415-460
+    positions: Vec<RangeInclusive<isize>>,
+
+    #[arg(short = 'm', long)]
+    /// File of positions or ranges of positions to exclude from comparison
+    mask: Option<PathBuf>,
+
+    #[arg(long, value_delimiter = ',', value_parser = parse_position_range)]
+    /// Read TGA as selenocysteine (U) at these alignment codon positions or ranges, e.g., 49
+    selenocysteine: Vec<RangeInclusive<isize>>,
+
+    #[arg(long, value_delimiter = ',', value_parser = parse_position_range)]
+    /// Read TAG as pyrrolysine (O) at these alignment codon positions or ranges
+    pyrrolysine: Vec<RangeInclusive<isize>>,
+
+    #[arg(long, requires = "mask")]
+    /// Mark differences at masked positions as `masked` instead of omitting them
+    mark_masked: bool,
+
+    #[arg(long)]
+    /// Mark the X and gap codons before a query's first and after its last called residue as `missing`, apart from internal X
+    mark_terminal_missing: bool,
+
+    #[arg(short = 'A', long)]
+    /// Tab-separated file of position ranges and labels to report in a `region` column
+    annotation: Option<PathBuf>,
+
+    #[arg(short = 'w', long)]
+    /// File of mutations of interest, e.g., NA:H275Y, to flag with `!` and summarize per sequence
+    watchlist: Option<PathBuf>,
+
+    #[arg(short = 's', long)]
+    /// Also report synonymous codon changes as `syn` and count synonymous and nonsynonymous changes
+    synonymous: bool,
+
+    #[arg(long)]
+    /// Add the Shannon entropy, number of distinct amino acids, and major-allele frequency of the queries to each row
+    stats: bool,
+
+    #[arg(long, conflicts_with = "extra_references")]
+    /// Collapse queries with identical differences into one column per cluster
+    cluster: bool,
+
+    #[arg(short = 'g', long, conflicts_with = "cluster")]
+    /// Two-column file of sequence names and groups, e.g., clades, to report per-group frequencies instead of sequences
+    groups: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Only report positions where at least this many sequences differ from the reference
+    min_count: Option<usize>,
+
+    #[arg(long, value_parser = parse_fraction)]
+    /// Only report positions where at least this fraction of sequences differ from the reference
+    min_freq: Option<f64>,
+
+    #[arg(long)]
+    /// Mark differences found in only one sequence with `^` and count them per sequence
+    private: bool,
+
+    #[arg(long)]
+    /// Append an `identity` row with each sequence's percent amino acid identity to the reference
+    identity: bool,
+
+    #[arg(long)]
+    /// Note the physicochemical change of each substitution, e.g., hydrophobic->positive, in JSON output
+    properties: bool,
+
+    #[arg(long)]
+    /// Note the BLOSUM62 score of each substitution in JSON output
+    blosum: bool,
+
+    #[arg(long)]
+    /// Only report substitutions whose BLOSUM62 score is at most the negative of this penalty
+    min_blosum_penalty: Option<i32>,
+
+    #[arg(long)]
+    /// Append a `glycosylation` row of the N-linked glycosylation sites each sequence gains or loses
+    glycosylation: bool,
+
+    #[arg(short = 'D', long)]
+    /// Protein domains, e.g., InterProScan TSV, to report in a `domain` column and count differences in
+    domains: Option<PathBuf>,
+
+    #[arg(long, requires = "emergence")]
+    /// Two-column file of sequence names and collection dates, e.g., 2024-03-15
+    dates: Option<PathBuf>,
+
+    #[arg(long, requires = "dates")]
+    /// Write the earliest date and monthly frequency of every mutation to this file
+    emergence: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Name of an input record to use as the ancestor, marking queries that revert to it with `<`
+    ancestor: Option<String>,
+
+    #[arg(long = "extra-reference", value_name = "NAME")]
+    /// Name of an input record to also diff every query against, in a column set of its own; may be repeated
+    extra_references: Vec<String>,
+
+    #[arg(long)]
+    /// Note the nucleotide changes underlying each difference, e.g., A346G, in JSON output
+    nucleotide_changes: bool,
+
+    #[arg(long)]
+    /// Write a tab-separated profile of the amino acid counts of the queries at every position to this file
+    profile: Option<PathBuf>,
+
+    #[arg(long, requires = "profile")]
+    /// Write frequencies instead of counts to the profile
+    profile_frequencies: bool,
+
+    #[arg(long)]
+    /// Never report unknown residues (X), including unresolved degenerate codons, as differences
+    ambiguous_matches_reference: bool,
+
+    #[arg(long, value_enum, default_value_t)]
+    /// Report deletions at every codon, ignore them, or collapse each run into one event, e.g., del158-160
+    deletions: DeletionPolicy,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Write the amino acid difference table, as when no subcommand is given
+    Diff(Box<DiffArgs>),
+    /// Open the difference table in a scrollable, searchable terminal viewer
+    View(Box<DiffArgs>),
+    /// Answer POST requests to /diff with the difference table of their records as JSON
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        /// Address to listen on; use 0.0.0.0:<PORT> to accept requests from other hosts
+        address: String,
+
+        #[arg(long, default_value = "4")]
+        /// Requests answered at once; as many more wait, and the rest are turned away with 503
+        workers: NonZeroUsize,
+
+        #[arg(long, default_value = "64M", value_parser = parse_size)]
+        /// Largest request body accepted, e.g., 512K or 1G; larger ones are answered with 413
+        max_body: u64,
+    },
+    /// Write the difference table of every FASTA file in a directory to a file of its own
+    Batch {
+        /// Directory of the FASTA files to diff, e.g., one per segment or sample
+        dir: PathBuf,
+
+        #[arg(long)]
+        /// Directory the tables are written to, named for their inputs, e.g., H3.csv for H3.fasta
+        out_dir: PathBuf,
+
+        #[arg(long, default_value = "4")]
+        /// Inputs diffed at once, each on its own thread, sharing them with its queries
+        jobs: NonZeroUsize,
+
+        #[arg(long)]
+        /// Compress each table with gzip as it is written, e.g., to H3.csv.gz
+        gzip: bool,
+
+        #[arg(long)]
+        /// Replace tables already in the output directory, which are otherwise an error
+        overwrite: bool,
+
+        #[arg(long)]
+        /// Skip the inputs whose tables an earlier run of the batch completed, as its manifest records
+        resume: bool,
+
+        #[command(flatten)]
+        diff: Box<DiffArgs>,
+    },
+    /// Emit the consensus of the input alignment as FASTA
+    Consensus {
+        #[arg(short = 't', long, value_parser = parse_fraction)]
+        /// Use the IUPAC code for the fewest bases making up at least this fraction of each column
+        threshold: Option<f64>,
+    },
+    /// Estimate dN/dS of each sequence against the reference (Nei–Gojobori)
+    Dnds {
+        #[arg(short = 'W', long)]
+        /// Also report each window of this many codons
+        window: Option<usize>,
+    },
+    /// Emit the pairwise amino acid distance matrix of all sequences
+    Distance {
+        #[arg(short = 'M', long, value_enum, default_value_t)]
+        /// How differences between residues are scored
+        metric: DistanceMetric,
+    },
+    /// Emit the translation of every input record as amino acid FASTA
+    Translate,
+    /// Emit the diversity of the query residues at every position: entropy, distinct residues, and major frequency
+    Stats,
+    /// Report where the translations of the same sequences differ between the input and another alignment
+    Compare {
+        /// The alignment to compare the input against
+        other: PathBuf,
+    },
+    /// Emit shell completions for aadiff
+    Completions {
+        /// The shell to complete in
+        shell: Shell,
+    },
+    /// Emit the man page of aadiff as roff
+    Man {
+        #[arg(long)]
+        /// Write aadiff.1 and a page per subcommand, e.g., aadiff-consensus.1, to this directory instead
+        dir: Option<PathBuf>,
+    },
+}
+
+/// How deletions are reported: at every codon, not at all, or as one event per run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeletionPolicy {
+    #[default]
+    Report,
+    Ignore,
+    Collapse,
+}
+
+/// What to do with a record named the same as an earlier one: fail, add a suffix,
+/// e.g., `name_2`, or drop it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    #[default]
+    Error,
+    Suffix,
+    Drop,
+}
+
+/// Which residues are compared when restricting to alignable regions: those within
+/// both the reference's and the query's own, those within every sequence's, or
+/// those within the reference's, however much of each query is ambiguous.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestrictMode {
+    #[default]
+    Pairwise,
+    Intersection,
+    Reference,
+}
+
+impl RestrictMode {
+    /// Whether each query is restricted to its own alignable region.
+    fn restricts_queries(restriction: Option<Self>) -> bool {
+        matches!(restriction, Some(RestrictMode::Pairwise | RestrictMode::Intersection))
+    }
+
+    /// Narrows the query ranges as the mode asks: to the reference's range, and,
+    /// for the intersection, to the range every sequence shares.
+    fn apply<'a>(restriction: Option<Self>, reference: &Range<usize>, queries: impl Iterator<Item = &'a mut Range<usize>>) {
+        let Some(mode) = restriction else {
+            return;
+        };
+        let mut queries = queries.collect::<Vec<_>>();
+        let shared = match mode {
+            RestrictMode::Pairwise => return,
+            RestrictMode::Intersection => queries.iter().fold(reference.clone(), |shared, range| {
+                shared.start.max(range.start)..shared.end.min(range.end)
+            }),
+            RestrictMode::Reference => reference.clone(),
+        };
+        for range in &mut queries {
+            let start = range.start.max(shared.start);
+            **range = start..range.end.min(shared.end).max(start);
+        }
+    }
+}
+
+/// What positions are numbered by: the codon columns of the alignment, or the
+/// residues of the ungapped reference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberBy {
+    #[default]
+    Alignment,
+    Reference,
+}
+
+/// What queries are compared against: the first record or the majority consensus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReferenceMode {
+    #[default]
+    First,
+    Consensus,
+}
+
+impl APDArgs {
+    /// How residues are restricted, by `--restrict`, or pairwise by `-r`.
+    pub(crate) fn restriction(&self) -> Option<RestrictMode> {
+        self.restrict
+            .or(self.restrict_to_pairwise_alignable.then_some(RestrictMode::Pairwise))
+    }
+
+    /// Checks that the outputs asked for can be written as asked: substitution notes
+    /// and metadata need JSON, and only delimited output can be appended to.
+    fn check_outputs(&self) -> Result<()> {
+        if (self.diff.properties || self.diff.blosum || self.diff.nucleotide_changes)
+            && self.diff.formatter.is_none()
+            && self.diff.output_format != Format::Json
+            && self.diff.output_json.is_none()
+        {
+            return Err(AadiffError::Invalid(
+                "Substitution notes need JSON output (-f json).".to_string(),
+            ));
+        }
+        if self.diff.json_metadata
+            && ((self.diff.output_format != Format::Json && self.diff.output_json.is_none())
+                || self.diff.json_schema != JsonSchema::V2)
+        {
+            return Err(AadiffError::Invalid(
+                "Metadata needs JSON output with schema v2 (-f json --json-schema v2).".to_string(),
+            ));
+        }
+        if self.diff.with_provenance
+            && self.diff.json_schema != JsonSchema::V2
+            && (self.diff.output_format == Format::Json || self.diff.output_json.is_some())
+        {
+            return Err(AadiffError::Invalid(
+                "Provenance in JSON output needs schema v2 (--json-schema v2).".to_string(),
+            ));
+        }
+        if self.append && self.diff.output_format != Format::Csv && self.writes_table() {
+            return Err(AadiffError::Invalid("Only delimited output can be appended to.".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Whether the arguments ask for the difference table rather than another
+    /// subcommand's output.
+    pub(crate) fn writes_table(&self) -> bool {
+        !self.validate && matches!(self.command, None | Some(Command::Diff(_)))
+    }
+
+    /// Whether the run reads its records from stdin, i.e., no input file or `-` is
+    /// given to a subcommand that reads any.
+    pub fn reads_stdin(&self) -> bool {
+        !matches!(
+            self.command,
+            Some(Command::Completions { .. } | Command::Man { .. } | Command::Serve { .. } | Command::Batch { .. })
+        ) && self.input_fasta.as_deref().is_none_or(|path| path == Path::new("-"))
+    }
+
+    /// The files named by the arguments, other than the config file, which is read
+    /// only while parsing the command line.
+    pub(crate) fn named_files(&self) -> impl Iterator<Item = &Path> {
+        let diff = &self.diff;
+        [
+            &self.input_fasta,
+            &self.include,
+            &self.output_xsv,
+            &self.segment_map,
+            &diff.output_csv,
+            &diff.output_json,
+            &diff.output_summary,
+            &diff.numbering,
+            &diff.mask,
+            &diff.annotation,
+            &diff.watchlist,
+            &diff.groups,
+            &diff.domains,
+            &diff.dates,
+            &diff.emergence,
+            &diff.profile,
+        ]
+        .into_iter()
+        .filter_map(Option::as_deref)
+    }
+
+    /// The input file to rerun on whenever it changes, with `--watch`.
+    pub fn watched(&self) -> Option<&Path> {
+        self.input_fasta.as_deref().filter(|_| self.watch)
+    }
+
+    /// Marks the arguments as those of a rerun under `--watch`, replacing the outputs
+    /// an earlier run wrote even without `--force`.
+    pub fn rerun(mut self) -> Self {
+        self.rerun = true;
+        self
+    }
+
+    /// The provenance of the run, with `--with-provenance`.
+    pub(crate) fn provenance(&self) -> Result<Option<&Provenance>> {
+        if !self.diff.with_provenance {
+            return Ok(None);
+        }
+        if let Some(provenance) = self.diff.provenance.get() {
+            return Ok(Some(provenance));
+        }
+        let provenance = Provenance::of(self)?;
+        Ok(Some(self.diff.provenance.get_or_init(|| provenance)))
+    }
+
+    /// The most verbose level to log at, given `-v` and `--quiet`.
+    pub fn log_level(&self) -> Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => Level::ERROR,
+            (false, 0) => Level::WARN,
+            (false, 1) => Level::INFO,
+            (false, 2) => Level::DEBUG,
+            (false, _) => Level::TRACE,
+        }
+    }
+}
+
+/// Runs `aadiff` as the command line does with `args`, writing to the output
+/// file or stdout.
+pub fn run(args: APDArgs) -> Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(Command::View(_)) = args.command {
+        return view::view(args);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(Command::Serve { .. }) = args.command {
+        return serve::serve(args);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(Command::Batch { .. }) = args.command {
+        return batch::batch(args);
+    }
+    diff(args, Io::Files)
+}
+
+/// Runs `aadiff` on FASTA records given in memory, giving what would otherwise be
+/// written to the output. Nothing is read from or written to disk, nor is the
+/// clock or terminal used, so this also runs where there are none, e.g., in a
+/// browser; options naming files are refused.
+pub fn run_in_memory(mut args: APDArgs, fasta: &[u8]) -> Result<Vec<u8>> {
+    if args.input_fasta.is_some() {
+        return Err(AadiffError::Invalid(
+            "Input given in memory cannot also be read from a file.".to_string(),
+        ));
+    }
+    // A config file named by the environment is only applied by the command line.
+    args.config = None;
+    args.no_progress = true;
+
+    let mut output = Vec::new();
+    diff(
+        args,
+        Io::Memory {
+            input:  fasta,
+            output: &mut output,
+        },
+    )?;
+    Ok(output)
+}
+
+/// Where a run reads its records and writes its tables, if not from and to the
+/// files its arguments name.
+pub(crate) enum Io<'a> {
+    Files,
+    /// Records are read from and tables written to memory, and no file is opened.
+    Memory {
+        input:  &'a [u8],
+        output: &'a mut dyn Write,
+    },
+    /// Records are read from the files the arguments name, and the table is written
+    /// to `output` rather than to `-o`.
+    Output(&'a mut dyn Write),
+    /// The difference table is sent to another thread instead of being written.
+    Channel(SyncSender<TableEvent>),
+}
+
+pub(crate) fn diff(mut args: APDArgs, io: Io<'_>) -> Result<()> {
+    let in_memory = matches!(io, Io::Memory { .. });
+    let start = (!in_memory).then(Instant::now);
+    if let Some(Command::Diff(diff)) = args.command.take_if(|c| matches!(c, Command::Diff(_))) {
+        args.diff = *diff;
+    }
+    if let Some(Command::View(_)) = args.command {
+        return Err(AadiffError::Invalid(
+            "The viewer needs a terminal; open it with `aadiff view`.".to_string(),
+        ));
+    }
+    if let Some(Command::Serve { .. }) = args.command {
+        return Err(AadiffError::Invalid(
+            "The server runs only from the command line, with `aadiff serve`.".to_string(),
+        ));
+    }
+    if let Some(Command::Batch { .. }) = args.command {
+        return Err(AadiffError::Invalid(
+            "Batches run only from the command line, with `aadiff batch`.".to_string(),
+        ));
+    }
+    if args.input_fasta.as_deref() == Some(Path::new("-")) {
+        if args.mmap || args.fai {
+            return Err(AadiffError::Invalid(
+                "--mmap and --fai need an input file, not stdin.".to_string(),
+            ));
+        }
+        args.input_fasta = None;
+    }
+    if in_memory {
+        let other = match &args.command {
+            Some(Command::Compare { other }) => Some(other.as_path()),
+            Some(Command::Man { dir }) => dir.as_deref(),
+            _ => None,
+        };
+        if let Some(path) = args.named_files().chain(other).next() {
+            return Err(AadiffError::Invalid(format!(
+                "Files such as '{}' cannot be named when running in memory.",
+                path.display()
+            )));
+        }
+        if args.diff.with_provenance {
+            return Err(AadiffError::Invalid(
+                "Provenance is only recorded for runs from the command line or files.".to_string(),
+            ));
+        }
+    }
+    let (input, output, sender) = match io {
+        Io::Files => (None, None, None),
+        Io::Memory { input, output } => (Some(input), Some(output), None),
+        Io::Output(output) => (None, Some(output), None),
+        Io::Channel(sender) => (None, None, Some(sender)),
+    };
+    let to_terminal = matches!((&output, &sender, &args.output_xsv), (None, None, None)) && stdout().is_terminal();
+    args.check_outputs()?;
+    if args.protein
+        && (args.diff.synonymous
+            || args.diff.nucleotide_changes
+            || matches!(args.command, Some(Command::Consensus { .. } | Command::Dnds { .. })))
+    {
+        return Err(AadiffError::Invalid(
+            "Protein input has no codons to compare; drop --protein or the options that need them.".to_string(),
+        ));
+    }
+    if let Some(threads) = args.threads {
+        // The pool is global, so later runs in the same process, e.g., with `--watch`,
+        // keep the one the first built.
+        static POOL: OnceLock<Result<(), String>> = OnceLock::new();
+        POOL.get_or_init(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()
+                .map_err(|e| e.to_string())
+        })
+        .clone()
+        .map_err(|e| AadiffError::Invalid(format!("Could not start the thread pool: {e}")))?;
+    }
+    let line_ending = if args.unix_line_endings { "" } else { "\r" };
+    let delim = args.output_delimiter.clone().unwrap_or_else(|| ",".to_string());
+    let delim = delim.as_str();
+
+    // Rows added to delimited output already holding some go under its header.
+    let headed = matches!(
+        args.command,
+        None | Some(
+            Command::Diff(_) | Command::Dnds { .. } | Command::Distance { .. } | Command::Stats | Command::Compare { .. }
+        )
+    ) && !args.validate;
+    let (writer, existing_header) = match (&args.output_xsv, output) {
+        (_, Some(output)) => (Either::Right(Either::Right(output)), None),
+        (Some(file_path), None) if sender.is_none() => {
+            let file = open_output(file_path, args.force || args.rerun, args.append)?;
+            let existing_header = if headed && args.append && file.metadata().map_err(open_error(file_path))?.len() > 0 {
+                let existing = OpenOptions::new().read(true).open(file_path).map_err(open_error(file_path))?;
+                Some(read_header(BufReader::new(existing)).map_err(|source| AadiffError::Read {
+                    kind: "output to append to",
+                    path: file_path.clone(),
+                    source,
+                })?)
+            } else {
+                None
+            };
+            (Either::Left(file), existing_header)
+        }
+        _ => (Either::Right(Either::Left(stdout())), None),
+    };
+    let mut writer = BufWriter::new(SkipHeader::new(writer, existing_header));
+
+    if let Some(Command::Completions { shell }) = args.command {
+        clap_complete::generate(shell, &mut config::command(), "aadiff", &mut writer);
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    if let Some(Command::Man { dir }) = &args.command {
+        let command = config::command();
+        match dir {
+            Some(dir) => clap_mangen::generate_to(command, dir).map_err(open_error(dir))?,
+            None => clap_mangen::Man::new(command)
+                .render(&mut writer)
+                .map_err(AadiffError::Output)?,
+        }
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    // Taken out now, so that the rest of the run only reads the arguments.
+    let formatter = args.diff.formatter.take();
+    let included = records::included(&args)?;
+    let mapped_input = records::open_mapped(&args)?;
+    let reading = Progress::records(!args.no_progress);
+    let reader = records::open(&args, input, mapped_input.as_ref(), included.as_ref(), &reading)?;
+
+    if args.validate {
+        let problems = validate::write_validation(reader, &args, &mut writer, line_ending)?;
+        reading.finish();
+        writer.flush().map_err(AadiffError::Output)?;
+        return match problems {
+            0 => Ok(()),
+            n => Err(AadiffError::Invalid(format!("Found {n} problems with the input."))),
+        };
+    }
+
+    if let Some(Command::Consensus { threshold }) = args.command {
+        consensus::write_consensus(reader, &mut writer, threshold, &args)?;
+        reading.finish();
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    if let Some(Command::Translate) = args.command {
+        translate::write_translations(reader, &args, &mut writer)?;
+        reading.finish();
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    if let Some(Command::Compare { other }) = &args.command {
+        let other = FastaLines::new(BufReader::new(
+            OpenOptions::new().read(true).open(other).map_err(open_error(other))?,
+        ));
+        compare::write_comparison(reader, other, &args, &mut writer, delim, line_ending)?;
+        reading.finish();
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    // Held in full, queries take up about twice the input: once as read and once
+    // gathered by column. Input of unknown size is streamed to stay within budget.
+    let low_memory = args.low_memory
+        || args.max_memory.is_some_and(|budget| {
+            let size = args
+                .input_fasta
+                .as_deref()
+                .and_then(|p| std::fs::metadata(p).ok())
+                .map(|m| m.len());
+            args.reference == ReferenceMode::First
+                && !args.align
+                && !args.segments
+                && args.segment_map.is_none()
+                && size.is_none_or(|size| size.saturating_mul(2) > budget)
+        });
+
+    let mapped = mapped_input.as_ref().filter(|_| {
+        args.command.is_none()
+            && !low_memory
+            && !args.align
+            && args.reference == ReferenceMode::First
+            && !args.segments
+            && args.segment_map.is_none()
+    });
+
+    let mut borrowed = Vec::new();
+    let mut stream = None;
+    let (dna_reference, queries, segments) = if let Some(mapped) = mapped {
+        let (dna_reference, queries) = records::borrow_queries(&args, mapped, included.as_ref(), &reading)?;
+        borrowed = queries;
+        (dna_reference, Vec::new(), Vec::new())
+    } else if low_memory && args.command.is_none() {
+        if args.reference != ReferenceMode::First {
+            return Err(AadiffError::Invalid(
+                "Low-memory mode needs the first record as the reference.".to_string(),
+            ));
+        }
+        let mut records = reader.map(|record| record.map(|r| recode(r, &args)));
+        let Some(dna_reference) = records.next().transpose().map_err(AadiffError::Input)? else {
+            return Err(AadiffError::Empty("No first record available!".to_string()));
+        };
+        stream = Some(records);
+        (dna_reference, Vec::new(), Vec::new())
+    } else {
+        read_sequences(&args, reader)?
+    };
+    // Streamed queries are still being read as they are diffed.
+    if stream.is_none() {
+        reading.finish();
+    }
+    let reference_length = dna_reference.sequence.len();
+    let queries = drop_malformed(&args, &dna_reference.name, reference_length, queries)?;
+    if stream.is_none() {
+        report_skipped(&args);
+    }
+    let mut names = UniqueNames::new(args.on_duplicate, &dna_reference.name);
+    let queries = names.admit_all(queries)?;
+    let mut unique = Vec::with_capacity(borrowed.len());
+    for mut r in borrowed {
+        if let Some(name) = names.admit(&r.name)? {
+            if name != r.name {
+                r.name = Cow::Owned(name);
+            }
+            unique.push(r);
+        }
+    }
+    let mut borrowed = unique;
+
+    // Every record is as long as the reference, so all are cut to its whole codons.
+    let length = whole_codons(&args, &dna_reference)?;
+    let dna_reference = trim_to(dna_reference, length);
+    let queries = queries.into_iter().map(|q| trim_to(q, length)).collect::<Vec<_>>();
+    for r in &mut borrowed {
+        r.sequence = match std::mem::take(&mut r.sequence) {
+            Cow::Borrowed(bases) => Cow::Borrowed(&bases[..length]),
+            Cow::Owned(mut bases) => {
+                bases.truncate(length);
+                Cow::Owned(bases)
+            }
+        };
+    }
+
+    let recoding = Recoding {
+        selenocysteine: &args.diff.selenocysteine,
+        pyrrolysine:    &args.diff.pyrrolysine,
+    };
+    let translate_reference =
+        |bases: &[u8]| recoding.residues(bases, translate(bases, !args.strict_translation, args.partial_codon_policy));
+    check_reference(
+        &args,
+        &dna_reference.name,
+        &translate_reference(dna_reference.sequence.as_bytes()),
+    )?;
+
+    if let Some(Command::Distance { metric }) = args.command {
+        distance::write_distances(&dna_reference, &queries, metric, &args, &mut writer, delim, line_ending)?;
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    if let Some(Command::Dnds { window }) = args.command {
+        dnds::write_dnds(&dna_reference, &queries, window, &args, &mut writer, delim, line_ending)?;
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    if let Some(Command::Stats) = args.command {
+        stats::write_stats(&dna_reference, &queries, &args, &mut writer, delim, line_ending)?;
+        writer.flush().map_err(AadiffError::Output)?;
+        return Ok(());
+    }
+
+    let ref_codons = dna_reference.sequence;
+    let reference = FastaAA {
+        name:     dna_reference.name,
+        sequence: translate_reference(ref_codons.as_bytes()),
+    };
+    let restriction = args.restriction();
+    let ref_range = get_valid_range(reference.sequence.iter().copied(), restriction.is_some())?;
+    let ref_orf_end = reference
+        .sequence
+        .iter()
+        .position(|&aa| aa == b'*')
+        .unwrap_or(reference.sequence.len());
+
+    let translator = Translator {
+        args:       &args,
+        recoding:   &recoding,
+        ref_codons: ref_codons.as_bytes(),
+        ref_orf_end,
+        restriction,
+        segments: &segments,
+        baseline: low_memory.then(|| {
+            Arc::new(Baseline {
+                residues: reference.sequence.clone(),
+                codons:   ref_codons.clone(),
+            })
+        }),
+    };
+
+    let mut other_sequences = Vec::new();
+    if let Some(records) = &mut stream {
+        // Only one chunk of queries is held in full at a time, taking up at most a
+        // quarter of any memory budget.
+        let chunk_size = args.max_memory.map_or(STREAM_CHUNK, |budget| {
+            let record = (ref_codons.len() as u64 * 2).max(1);
+            usize::try_from(budget / 4 / record).unwrap_or(usize::MAX).max(1)
+        });
+        loop {
+            let mut read = 0;
+            let mut chunk = Vec::new();
+            for record in records.by_ref().take(chunk_size) {
+                read += 1;
+                chunk.extend(recover(&args, record)?);
+            }
+            if read == 0 {
+                break;
+            }
+            other_sequences.extend(
+                names
+                    .admit_all(drop_malformed(
+                        &args,
+                        &reference.name,
+                        reference_length,
+                        drop_failing_qc(&args, chunk),
+                    )?)?
+                    .into_par_iter()
+                    .map(|q| translator.store(trim_to(q, length)))
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+        report_skipped(&args);
+    } else if mapped.is_some() {
+        let rows = borrowed.iter().map(|r| r.sequence.as_ref()).collect::<Vec<_>>();
+        let columns = Arc::new(Columns::gather(reference.sequence.len(), &rows));
+        other_sequences = borrowed
+            .par_iter()
+            .enumerate()
+            .map(|(index, r)| {
+                let sequence = QuerySequence::Column {
+                    columns: Arc::clone(&columns),
+                    index,
+                    resolve: !args.strict_translation,
+                    partial: args.partial_codon_policy,
+                };
+                translator.validate(r.name.to_string(), sequence, translator.annotate(&r.sequence))
+            })
+            .collect::<Result<_>>()?;
+        drop(std::mem::take(&mut borrowed));
+    } else {
+        other_sequences = queries.into_par_iter().map(|q| translator.store(q)).collect::<Result<_>>()?;
+    }
+    reading.finish();
+    match start {
+        Some(start) => info!(
+            "Read and translated {} queries in {:.2?}",
+            other_sequences.len(),
+            start.elapsed()
+        ),
+        None => info!("Read and translated {} queries", other_sequences.len()),
+    }
+
+    let mut take_baseline = |name: &str, role: &str| {
+        let Some(index) = other_sequences.iter().position(|q| q.name == name) else {
+            return Err(AadiffError::Invalid(format!("No record named '{name}' to use as {role}.")));
+        };
+        Ok(other_sequences.remove(index).sequence.residues())
+    };
+    let ancestor = args
+        .diff
+        .ancestor
+        .as_deref()
+        .map(|name| take_baseline(name, "the ancestor"))
+        .transpose()?;
+    let extra_references = args
+        .diff
+        .extra_references
+        .iter()
+        .map(|name| Ok((name.clone(), take_baseline(name, "a reference")?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    RestrictMode::apply(
+        restriction,
+        &ref_range,
+        other_sequences.iter_mut().map(|s| &mut s.valid_range),
+    );
+
+    // Positions are compared one at a time, so queries are stored column by column.
+    if !low_memory {
+        store_by_column(
+            other_sequences.iter_mut().map(|s| &mut s.sequence).collect(),
+            reference.sequence.len(),
+        );
+    }
+
+    // Reference-gap columns have no number of their own in reference coordinates, so
+    // they are left out, or reported as insertions with `-I`.
+    let positions = Positions::new(&args, &reference.sequence, ref_codons.as_bytes(), &segments)?;
+
+    let regions = args.diff.annotation.as_deref().map(Regions::read).transpose()?;
+    let domains = args.diff.domains.as_deref().map(Regions::read_domains).transpose()?;
+
+    let groups = args
+        .diff
+        .groups
+        .as_deref()
+        .map(|path| Groups::read(path, other_sequences.iter().map(|s| s.name.as_str())))
+        .transpose()?;
+
+    let mut table = TableWriter::open(writer, &args, formatter, to_terminal, sender)?;
+    let column_name = |name: &str| {
+        let name = match &args.diff.name_format {
+            Some(template) => format_name(template, name),
+            None => name.to_string(),
+        };
+        if args.diff.sanitize_names {
+            sanitize_name(&name)
+        } else {
+            name
+        }
+    };
+    // Rendered names can repeat where the records' own did not, so they are made
+    // unique again.
+    let reference_column = column_name(&reference.name);
+    let mut columns = UniqueNames::new(args.on_duplicate, &reference_column);
+    let query_columns = other_sequences
+        .iter()
+        .map(|s| columns.column(&column_name(&s.name)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut extra_columns = Vec::new();
+    for (name, _) in &extra_references {
+        let name = column_name(name);
+        extra_columns.push(columns.column(&name)?);
+        for query in &query_columns {
+            extra_columns.push(columns.column(&format!("{query} vs {name}"))?);
+        }
+    }
+    table
+        .header(
+            std::iter::once(reference_column)
+                .chain(match &groups {
+                    Some(groups) => groups.names().to_vec(),
+                    None => query_columns,
+                })
+                .collect(),
+            regions
+                .iter()
+                .map(|_| "region")
+                .chain(domains.iter().map(|_| "domain"))
+                .chain(
+                    args.diff
+                        .stats
+                        .then_some(["entropy", "distinct", "major_frequency"])
+                        .into_iter()
+                        .flatten(),
+                )
+                .map(str::to_string)
+                .chain(extra_columns)
+                .collect(),
+        )
+        .map_err(AadiffError::Output)?;
+
+    // With only the reference, the table is its header alone, e.g., `{}` in JSON.
+    if other_sequences.is_empty() {
+        warn!("No queries are left to compare with '{}'", reference.name);
+        table.finish().map_err(AadiffError::Output)?;
+        if args.diff.exit_code {
+            return Err(AadiffError::Empty("No queries available!".to_string()));
+        }
+        return Ok(());
+    }
+
+    let mask = args
+        .diff
+        .mask
+        .as_deref()
+        .map(read_position_ranges)
+        .transpose()?
+        .unwrap_or_default();
+    let watchlist = args.diff.watchlist.as_deref().map(Watchlist::read).transpose()?;
+    if !positions.has_genes()
+        && let Some(name) = watchlist.as_ref().and_then(Watchlist::first_with_gene)
+    {
+        warn!("Positions are not labeled with genes without segments or --numbering, so '{name}' will never match");
+    }
+    let mut emergence = match args.diff.dates.as_deref() {
+        Some(path) => Some(Emergence::new(
+            &read_dates(path)?,
+            other_sequences.iter().map(|s| s.name.as_str()),
+        )),
+        None => None,
+    };
+    // Opened before diffing so that an existing report is refused before any work.
+    let emergence_output = match (&emergence, &args.diff.emergence) {
+        (Some(_), Some(path)) => Some(BufWriter::new(open_output(path, args.force || args.rerun, false)?)),
+        _ => None,
+    };
+    let mut profile = match &args.diff.profile {
+        Some(path) => {
+            let mut writer = BufWriter::new(open_output(path, args.force || args.rerun, false)?);
+            write_profile_header(&mut writer, line_ending).map_err(AadiffError::Output)?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut tallies = other_sequences
+        .iter()
+        .map(|_| Tally {
+            domains: vec![0; domains.as_ref().map_or(0, Regions::len)],
+            ..Tally::default()
+        })
+        .collect::<Vec<_>>();
+
+    // A vectorized pre-scan finds the codons where any query differs from the reference.
+    let variable = variable_codons(ref_codons.as_bytes(), other_sequences.iter().map(|s| &s.sequence));
+
+    let comparison = Comparison {
+        args:      &args,
+        recoding:  &recoding,
+        reference: &reference.sequence,
+        ref_range: ref_range.clone(),
+        positions: &positions,
+        watchlist: watchlist.as_ref(),
+        ancestor:  ancestor.as_ref(),
+        domains:   domains.as_ref(),
+        emergence: emergence.is_some(),
+    };
+    let mut differences = 0;
+    let diffing = Progress::positions(!args.no_progress, ref_range.len());
+    for i in ref_range.clone() {
+        diffing.inc();
+        let ref_aa = reference.sequence[i];
+        if positions.by_reference() && ref_aa == b'-' {
+            continue;
+        }
+        if !args.diff.positions.is_empty() && !args.diff.positions.iter().any(|r| r.contains(&positions.numeric(i))) {
+            continue;
+        }
+        let masked = mask.iter().any(|r| r.contains(&positions.numeric(i)));
+        if masked && !args.diff.mark_masked {
+            continue;
+        }
+
+        let label = positions.label(i);
+        if let Some(profile) = &mut profile
+            && !masked
+        {
+            let observed = other_sequences
+                .iter()
+                .filter(|s| s.compares(i))
+                .map(|s| s.sequence.residue(i));
+            write_profile_row(profile, &label, observed, args.diff.profile_frequencies, line_ending)
+                .map_err(AadiffError::Output)?;
+        }
+
+        // Where every query has the reference codon nothing can differ, so the column
+        // only counts toward identity and none of its codons need translating.
+        let ref_codon = [ref_codons[i * 3], ref_codons[i * 3 + 1], ref_codons[i * 3 + 2]];
+        if !variable[i] && extra_references.iter().all(|(_, baseline)| baseline[i] == ref_aa) {
+            let skipped = args.partial_codon_policy == PartialCodonPolicy::Skip && is_partially_gapped(ref_codon);
+            let coded = args.protein && ambiguity_code(ref_codon).is_some();
+            let counted = !masked && !skipped && (coded || !matches!(ref_aa, b'X' | b'-'));
+            for (_, tally) in other_sequences
+                .iter()
+                .zip(&mut tallies)
+                .filter(|(s, _)| counted && s.compares(i))
+            {
+                tally.compared += 1;
+                tally.identical += 1;
+            }
+            continue;
+        }
+
+        let site = Site {
+            i,
+            label: &label,
+            ref_aa,
+            ref_codon,
+            masked,
+        };
+        let (mut cells, mutations): (Vec<_>, Vec<_>) = other_sequences
+            .par_iter()
+            .zip(tallies.par_iter_mut())
+            .map(|(seq, tally)| comparison.compare(&site, seq, tally))
+            .unzip();
+
+        if let Some(emergence) = &mut emergence {
+            for (q, mutation) in mutations.into_iter().enumerate() {
+                if let Some(mutation) = mutation {
+                    emergence.record(mutation, q);
+                }
+            }
+        }
+
+        if args.diff.private {
+            let mut counts = HashMap::new();
+            for cell in cells.iter().flatten() {
+                *counts.entry(cell.trim_end_matches(['!', '<']).to_string()).or_insert(0) += 1;
+            }
+
+            for (cell, tally) in cells.iter_mut().zip(&mut tallies) {
+                if let Some(cell) = cell
+                    && !matches!(cell.trim_end_matches(['!', '<']), "syn" | "fs" | "masked" | "missing")
+                    && counts[cell.trim_end_matches(['!', '<'])] == 1
+                {
+                    tally.private += 1;
+                    cell.push('^');
+                }
+            }
+        }
+
+        // Only residues that really differ count toward --min-count and --min-freq.
+        let differing = cells
+            .iter()
+            .flatten()
+            .filter(|c| !matches!(c.trim_end_matches(['!', '<', '^']), "syn" | "masked" | "missing" | "fs"))
+            .count();
+        let too_rare = args.diff.min_count.is_some_and(|n| differing < n)
+            || args
+                .diff
+                .min_freq
+                .is_some_and(|f| (differing as f64) < f * other_sequences.len() as f64);
+
+        let extra_cells = extra_cells(&args, i, masked, &extra_references, &other_sequences);
+        let extra_differs = extra_cells
+            .chunks(other_sequences.len() + 1)
+            .any(|set| set[1..].iter().any(Option::is_some));
+
+        if (cells.iter().any(Option::is_some) || extra_differs) && !too_rare {
+            differences += 1;
+            let mut extras = regions
+                .iter()
+                .chain(&domains)
+                .map(|r| r.label(positions.numeric(i)))
+                .collect::<Vec<_>>();
+            if args.diff.stats {
+                let observed = other_sequences
+                    .iter()
+                    .filter(|s| s.compares(i))
+                    .map(|s| s.sequence.residue(i));
+                extras.extend(Diversity::of(observed).map_or([None, None, None], |d| d.cells()));
+            }
+            extras.extend(extra_cells);
+
+            let ref_aa = match ambiguity_code(ref_codon) {
+                Some(code) if args.protein => code as char,
+                _ => ref_aa as char,
+            }
+            .to_string();
+            if let Some(groups) = &groups {
+                let observed = other_sequences.iter().zip(&cells).map(|(s, cell)| {
+                    s.compares(i).then(|| match cell.as_deref() {
+                        Some(cell) if cell != "syn" => cell.trim_end_matches(['!', '<', '^']),
+                        _ => ref_aa.as_str(),
+                    })
+                });
+                table
+                    .row(PositionDiff::new(&label, &ref_aa, groups.frequencies(observed), extras))
+                    .map_err(AadiffError::Output)?;
+            } else if args.diff.properties || args.diff.blosum || args.diff.nucleotide_changes {
+                let notes = substitution_notes(
+                    &args,
+                    i,
+                    &reference.sequence,
+                    ref_codons.as_bytes(),
+                    &positions,
+                    &other_sequences,
+                    &cells,
+                );
+                table
+                    .row(PositionDiff::new(&label, &ref_aa, cells, extras).with_notes(notes))
+                    .map_err(AadiffError::Output)?;
+            } else {
+                table
+                    .row(PositionDiff::new(&label, &ref_aa, cells, extras))
+                    .map_err(AadiffError::Output)?;
+            }
+        }
+    }
+    diffing.finish();
+
+    if let Some(mut profile) = profile {
+        profile.flush().map_err(AadiffError::Output)?;
+    }
+
+    if let (Some(emergence), Some(mut writer)) = (emergence, emergence_output) {
+        emergence
+            .write(&mut writer, delim, line_ending)
+            .map_err(AadiffError::Output)?;
+        writer.flush().map_err(AadiffError::Output)?;
+    }
+
+    if groups.is_some() {
+        // Per-sequence summaries do not apply to group columns.
+        table.finish().map_err(AadiffError::Output)?;
+        if args.diff.exit_code && differences == 0 {
+            return Err(AadiffError::NoDifferences);
+        }
+        return Ok(());
+    }
+
+    Summary {
+        reference: &reference.sequence,
+        ref_range,
+        ref_orf_end,
+        restricted: restriction.is_some(),
+        queries:    &other_sequences,
+        tallies:    &tallies,
+        positions:  &positions,
+        domains:    domains.as_ref(),
+    }
+    .write(&args, &mut table)?;
+
+    table.finish().map_err(AadiffError::Output)?;
+    if let Some(start) = start {
+        info!("Wrote the difference table in {:.2?}", start.elapsed());
+    }
+    if args.diff.exit_code && differences == 0 {
+        return Err(AadiffError::NoDifferences);
+    }
+    Ok(())
+}
+
+/// Opens the output file, refusing to overwrite one that exists unless forced to
+/// or appending to it.
+fn open_output(path: &Path, force: bool, append: bool) -> Result<std::fs::File> {
+    let mut options = OpenOptions::new();
+    match (force, append) {
+        (_, true) => options.append(true).create(true),
+        (true, false) => options.write(true).create(true).truncate(true),
+        (false, false) => options.write(true).create_new(true),
+    };
+    options.open(path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::AlreadyExists => AadiffError::Invalid(format!(
+            "'{}' already exists; give --force to overwrite it or --append to add to it.",
+            path.display()
+        )),
+        _ => open_error(path)(source),
+    })
+}
+
+/// Attaches the path of a file that could not be opened to the error.
+fn open_error(path: &Path) -> impl FnOnce(std::io::Error) -> AadiffError + '_ {
+    |source| AadiffError::Open {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Parses a number of bytes with an optional binary suffix, e.g., `512M` or `8GB`.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (number, scale) = match digits.char_indices().last() {
+        Some((k, 'K')) => (&digits[..k], 1 << 10),
+        Some((k, 'M')) => (&digits[..k], 1 << 20),
+        Some((k, 'G')) => (&digits[..k], 1 << 30),
+        Some((k, 'T')) => (&digits[..k], 1 << 40),
+        _ => (digits, 1),
+    };
+    match number.trim().parse::<u64>().ok().and_then(|n| n.checked_mul(scale)) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(format!("'{s}' is not a size such as 512M or 8G")),
+    }
+}
+
+/// Parses a field delimiter, which may be `tab` or hold `\t` escapes so that no
+/// literal tab has to get through a shell or workflow manager.
+fn parse_delimiter(s: &str) -> Result<String, String> {
+    let delim = match s {
+        "tab" => "\t".to_string(),
+        _ => s.replace("\\t", "\t"),
+    };
+    if delim.is_empty() || delim.contains(['"', '\n', '\r']) {
+        return Err(format!("'{}' cannot separate fields", s.escape_default()));
+    }
+    Ok(delim)
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if f > 0.0 && f <= 1.0 => Ok(f),
+        _ => Err(format!("'{s}' is not a fraction in (0, 1]")),
+    }
+}
+
+/// The range of residues compared: all of them, or, when `restrict` is set, those
+/// from the first to the last that is neither `X` nor a gap. Restricting a sequence
+/// without any such residue is an error.
+pub fn get_valid_range(
+    residues: impl DoubleEndedIterator<Item = u8> + ExactSizeIterator + Clone, restrict: bool,
+) -> Result<Range<usize>> {
+    if restrict {
+        let (Some(s), Some(e)) = (
+            residues.clone().position(|aa| aa != b'X' && aa != b'-'),
+            residues.clone().rposition(|aa| aa != b'X' && aa != b'-'),
+        ) else {
+            return Err(AadiffError::Invalid(
+                "Sequence doesn't contain valid data for comparison.".to_string(),
+            ));
+        };
+
+        Ok(s..e + 1)
+    } else {
+        Ok(0..residues.len())
+    }
+}
@@ -0,0 +1,88 @@
+use memmap2::Mmap;
+use std::{borrow::Cow, fs::File, io, path::Path, sync::Arc};
+use zoe::prelude::*;
+
+/// FASTA records parsed directly from a memory-mapped file, without copying the
+/// file through a read buffer first. Clones share the map, each reading from where
+/// the original had got to.
+#[derive(Clone)]
+pub(crate) struct MappedFasta {
+    map:    Arc<Mmap>,
+    offset: usize,
+}
+
+/// A FASTA record borrowed from a mapped file. Its sequence is a slice of the map
+/// when it is written on a single line, and is only copied when lines must be joined.
+pub(crate) struct FastaRecord<'a> {
+    pub(crate) name:     Cow<'a, str>,
+    pub(crate) sequence: Cow<'a, [u8]>,
+}
+
+impl MappedFasta {
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is only read. As with any mapped file, it must not be
+        // truncated by another process while it is being read.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedFasta {
+            map:    Arc::new(map),
+            offset: 0,
+        })
+    }
+
+    /// Every record of the file, borrowed from the map.
+    pub(crate) fn records(&self) -> impl Iterator<Item = io::Result<FastaRecord<'_>>> {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let (record, length) = parse_record(&self.map, offset)?;
+            offset += length;
+            Some(record)
+        })
+    }
+}
+
+/// Parses the record at `offset` into the mapped file, giving it along with the
+/// number of bytes it takes up. Text that is not a record is reported by its line
+/// number and skipped up to the next header.
+fn parse_record(map: &[u8], offset: usize) -> Option<(io::Result<FastaRecord<'_>>, usize)> {
+    let data = &map[offset..];
+    let start = data.iter().position(|b| !b.is_ascii_whitespace())?;
+    let record = &data[start..];
+    if record[0] != b'>' {
+        let line = map[..offset + start].iter().filter(|&&b| b == b'\n').count() + 1;
+        let error = io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {line}: expected a FASTA header starting with '>'"),
+        );
+        let skipped = record.windows(2).position(|w| w == b"\n>").map_or(record.len(), |p| p + 1);
+        return Some((Err(error), start + skipped));
+    }
+
+    let header_end = record.iter().position(|&b| b == b'\n').unwrap_or(record.len());
+    let record_end = record[header_end..]
+        .windows(2)
+        .position(|w| w == b"\n>")
+        .map_or(record.len(), |p| header_end + p + 1);
+
+    let name = String::from_utf8_lossy(record[1..header_end].trim_ascii());
+    let bases = record[header_end..record_end].trim_ascii();
+    let sequence = if bases.iter().any(u8::is_ascii_whitespace) {
+        Cow::Owned(bases.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect())
+    } else {
+        Cow::Borrowed(bases)
+    };
+    Some((Ok(FastaRecord { name, sequence }), start + record_end))
+}
+
+impl Iterator for MappedFasta {
+    type Item = io::Result<FastaSeq>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (record, length) = parse_record(&self.map, self.offset)?;
+        self.offset += length;
+        Some(record.map(|r| FastaSeq {
+            name:     r.name.into_owned(),
+            sequence: r.sequence.into_owned(),
+        }))
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    DuplicatePolicy,
+    error::{AadiffError, Result},
+};
+use std::collections::{HashMap, HashSet};
+use tracing::warn;
+use zoe::data::fasta::FastaNT;
+
+/// A part of a `--name-format` template.
+enum Piece<'a> {
+    Text(&'a str),
+    /// The whole record name
+    Name,
+    /// The record name up to its first whitespace
+    Id,
+    /// The 1-based field of the record name split on `|`
+    Field(usize),
+}
+
+fn pieces(template: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        if open > 0 {
+            pieces.push(Piece::Text(&rest[..open]));
+        }
+        let Some(close) = rest[open..].find('}') else {
+            return Err(format!("'{template}' has an unclosed '{{'"));
+        };
+        let placeholder = &rest[open + 1..open + close];
+        pieces.push(match placeholder {
+            "name" => Piece::Name,
+            "id" => Piece::Id,
+            _ => match placeholder.strip_prefix("field:").map(str::parse::<usize>) {
+                Some(Ok(n)) if n > 0 => Piece::Field(n),
+                _ => {
+                    return Err(format!(
+                        "'{{{placeholder}}}' is not {{name}}, {{id}}, or {{field:N}} counting from 1"
+                    ));
+                }
+            },
+        });
+        rest = &rest[open + close + 1..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+/// Checks a `--name-format` template, e.g., `{field:2}|{field:4}`.
+pub(crate) fn parse_name_format(s: &str) -> Result<String, String> {
+    pieces(s)?;
+    Ok(s.to_string())
+}
+
+/// Renders a record name through a checked template: `{name}` is the whole name,
+/// `{id}` its first word, and `{field:N}` its Nth field split on `|`, empty if it
+/// has fewer. Names rendering empty are kept whole.
+pub(crate) fn format_name(template: &str, name: &str) -> String {
+    let Ok(pieces) = pieces(template) else {
+        return name.to_string();
+    };
+    let mut formatted = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => formatted.push_str(text),
+            Piece::Name => formatted.push_str(name),
+            Piece::Id => formatted.push_str(name.split_whitespace().next().unwrap_or_default()),
+            Piece::Field(n) => formatted.push_str(name.split('|').nth(n - 1).unwrap_or_default().trim()),
+        }
+    }
+    if formatted.is_empty() { name.to_string() } else { formatted }
+}
+
+/// Replaces every character of a name that is not printable ASCII, e.g., a tab, a
+/// newline, or an accented letter, with `_`, for `--sanitize-names`.
+pub(crate) fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_graphic() || c == ' ' { c } else { '_' })
+        .collect()
+}
+
+/// Escapes the control characters of a name, e.g., a tab as `\t`, so that it
+/// stays on one line of plain text.
+pub(crate) fn printable_name(name: &str) -> String {
+    let mut printable = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_control() {
+            printable.extend(c.escape_default());
+        } else {
+            printable.push(c);
+        }
+    }
+    printable
+}
+
+/// The record names taken so far, reference first, for giving every column its
+/// own name under `--on-duplicate`.
+pub(crate) struct UniqueNames {
+    policy: DuplicatePolicy,
+    taken:  HashSet<String>,
+    /// The next suffix to try for each repeated name
+    next: HashMap<String, usize>,
+}
+
+impl UniqueNames {
+    pub(crate) fn new(policy: DuplicatePolicy, reference: &str) -> Self {
+        UniqueNames {
+            policy,
+            taken: HashSet::from([reference.to_string()]),
+            next:  HashMap::new(),
+        }
+    }
+
+    /// The name to give a record: its own if not yet taken, otherwise, per the
+    /// policy, an error, the name with the first free suffix, e.g., `name_2`, or
+    /// `None` to drop the record.
+    pub(crate) fn admit(&mut self, name: &str) -> Result<Option<String>> {
+        if self.taken.insert(name.to_string()) {
+            return Ok(Some(name.to_string()));
+        }
+
+        match self.policy {
+            DuplicatePolicy::Error => Err(AadiffError::Invalid(format!(
+                "More than one record is named '{name}', so their columns could not be told apart. Rename them, or \
+                 give --on-duplicate suffix or drop."
+            ))),
+            DuplicatePolicy::Drop => {
+                warn!("Dropping a record repeating the name '{name}'");
+                Ok(None)
+            }
+            DuplicatePolicy::Suffix => {
+                let renamed = self.suffix(name);
+                warn!("Renaming a record repeating the name '{name}' to '{renamed}'");
+                Ok(Some(renamed))
+            }
+        }
+    }
+
+    /// The name to give a column once rendered by `--name-format` and
+    /// `--sanitize-names`, which may repeat another though the records' own names
+    /// differ. A repeat is an error under the `error` policy, and otherwise gets the
+    /// first free suffix, as every record's column is kept.
+    pub(crate) fn column(&mut self, name: &str) -> Result<String> {
+        if self.taken.insert(name.to_string()) {
+            return Ok(name.to_string());
+        }
+
+        if self.policy == DuplicatePolicy::Error {
+            return Err(AadiffError::Invalid(format!(
+                "More than one column is named '{name}' once formatted, so they could not be told apart. Change \
+                 --name-format, or give --on-duplicate suffix."
+            )));
+        }
+        let renamed = self.suffix(name);
+        warn!("Renaming a column repeating the name '{name}' to '{renamed}'");
+        Ok(renamed)
+    }
+
+    /// Takes the name with the first free suffix, e.g., `name_2`.
+    fn suffix(&mut self, name: &str) -> String {
+        let next = self.next.entry(name.to_string()).or_insert(2);
+        let mut renamed = format!("{name}_{next}");
+        while !self.taken.insert(renamed.clone()) {
+            *next += 1;
+            renamed = format!("{name}_{next}");
+        }
+        *next += 1;
+        renamed
+    }
+
+    /// Renames or drops the duplicates among `records`, as [`admit`](Self::admit) does.
+    pub(crate) fn admit_all(&mut self, records: Vec<FastaNT>) -> Result<Vec<FastaNT>> {
+        let mut kept = Vec::with_capacity(records.len());
+        for mut record in records {
+            if let Some(name) = self.admit(&record.name)? {
+                record.name = name;
+                kept.push(record);
+            }
+        }
+        Ok(kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_names_through_templates() {
+        let name = "A/Texas/50/2012 | EPI_ISL_1 |H3N2";
+        assert_eq!(format_name("{field:2}", name), "EPI_ISL_1");
+        assert_eq!(format_name("{id}_{field:3}", name), "A/Texas/50/2012_H3N2");
+        assert_eq!(format_name("{name}", name), name);
+        // A name rendering empty is kept whole.
+        assert_eq!(format_name("{field:9}", name), name);
+        assert!(parse_name_format("{field:0}").is_err());
+        assert!(parse_name_format("{field:2").is_err());
+        assert!(parse_name_format("{accession}").is_err());
+    }
+
+    #[test]
+    fn sanitizes_and_escapes_names() {
+        assert_eq!(sanitize_name("A/Zürich\t1"), "A/Z_rich_1");
+        assert_eq!(printable_name("A\tB\nC"), "A\\tB\\nC");
+    }
+
+    #[test]
+    fn admits_repeated_names_per_policy() {
+        let mut names = UniqueNames::new(DuplicatePolicy::Suffix, "ref");
+        let admitted = ["q", "q", "q_2", "q", "ref"]
+            .iter()
+            .map(|n| names.admit(n).unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(admitted, ["q", "q_2", "q_2_2", "q_3", "ref_2"]);
+        // Rendered columns repeating a name are suffixed past every name taken.
+        assert_eq!(names.column("q").unwrap(), "q_4");
+
+        let mut names = UniqueNames::new(DuplicatePolicy::Drop, "ref");
+        assert_eq!(names.admit("q").unwrap().as_deref(), Some("q"));
+        assert_eq!(names.admit("q").unwrap(), None);
+        // Columns are never dropped, so a repeat is suffixed instead.
+        assert_eq!(names.column("q").unwrap(), "q_2");
+
+        let mut names = UniqueNames::new(DuplicatePolicy::Error, "ref");
+        assert!(names.admit("ref").is_err());
+        assert!(names.column("ref").is_err());
+    }
+}
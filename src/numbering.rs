@@ -0,0 +1,264 @@
+use crate::{
+    APDArgs, NumberBy,
+    error::{AadiffError, Result},
+    insertions::ungapped_positions,
+    segments::Segment,
+};
+use std::{collections::HashMap, ops::RangeInclusive, path::Path};
+use zoe::prelude::*;
+
+/// How the codons of the alignment are numbered and labeled in the table.
+pub(crate) struct Positions<'a> {
+    /// Whether positions count the reference's residues rather than the alignment's
+    /// columns, leaving reference gaps without a number of their own
+    by_reference:  bool,
+    ref_positions: Vec<usize>,
+    ref_codons:    &'a [u8],
+    offset:        isize,
+    labels:        Option<HashMap<usize, String>>,
+    segments:      &'a [Segment],
+}
+
+impl<'a> Positions<'a> {
+    pub(crate) fn new(
+        args: &APDArgs, reference: &AminoAcids, ref_codons: &'a [u8], segments: &'a [Segment],
+    ) -> Result<Self> {
+        Ok(Self {
+            by_reference:  args.diff.insertions || args.diff.number_by == NumberBy::Reference,
+            ref_positions: ungapped_positions(reference),
+            ref_codons,
+            offset: args
+                .diff
+                .position_offset
+                .or(args.diff.first_position.map(|p| p - 1))
+                .unwrap_or(0),
+            labels: args.diff.numbering.as_deref().map(read_numbering).transpose()?,
+            segments,
+        })
+    }
+
+    pub(crate) fn by_reference(&self) -> bool {
+        self.by_reference
+    }
+
+    pub(crate) fn offset(&self) -> isize {
+        self.offset
+    }
+
+    /// Whether positions are labeled by genes, from segments or a numbering file.
+    pub(crate) fn has_genes(&self) -> bool {
+        !self.segments.is_empty() || self.labels.is_some()
+    }
+
+    fn segment(&self, i: usize) -> Option<&Segment> {
+        self.segments.iter().find(|s| s.codons.contains(&i))
+    }
+
+    /// The number of the codon at `i`, counted from the start of its segment.
+    pub(crate) fn numeric(&self, i: usize) -> isize {
+        let start = self.segment(i).map_or(0, |s| s.codons.start);
+        let p = if self.by_reference {
+            self.ref_positions[i] - start.checked_sub(1).map_or(0, |s| self.ref_positions[s])
+        } else {
+            i - start + 1
+        };
+        p as isize + self.offset
+    }
+
+    /// The label of the codon at `i`, from the numbering file or its number, prefixed
+    /// with its segment.
+    pub(crate) fn label(&self, i: usize) -> String {
+        let label = self
+            .labels
+            .as_ref()
+            .and_then(|n| n.get(&(i + 1)).cloned())
+            .unwrap_or_else(|| self.numeric(i).to_string());
+        match self.segment(i) {
+            Some(segment) => format!("{}:{label}", segment.name),
+            None => label,
+        }
+    }
+
+    /// The number of the base at `j`.
+    pub(crate) fn nucleotide(&self, j: usize) -> usize {
+        if self.by_reference {
+            self.ref_codons[..=j].iter().filter(|&&b| b != b'-').count()
+        } else {
+            j + 1
+        }
+    }
+}
+
+/// Reads a two-column mapping from 1-based alignment codon positions to position
+/// labels, e.g., H3 numbering. Columns may be separated by tabs, commas, or spaces.
+/// Blank lines, `#` comments, and a header line are skipped.
+pub(crate) fn read_numbering(path: &Path) -> Result<HashMap<usize, String>> {
+    let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+        kind: "numbering file",
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut numbering = HashMap::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line
+            .split(|c: char| c == '\t' || c == ',' || c.is_whitespace())
+            .filter(|f| !f.is_empty());
+        let parse_error = |message: String| AadiffError::Parse {
+            kind: "Numbering file",
+            path: path.to_path_buf(),
+            line: n + 1,
+            message,
+        };
+        let (Some(position), Some(label)) = (fields.next(), fields.next()) else {
+            return Err(parse_error("expected a position and a label".to_string()));
+        };
+
+        match position.parse::<usize>() {
+            Ok(p) => {
+                numbering.insert(p, label.to_string());
+            }
+            Err(_) if numbering.is_empty() => {}
+            Err(_) => return Err(parse_error(format!("invalid position '{position}'"))),
+        }
+    }
+    Ok(numbering)
+}
+
+/// Parses a single position, e.g., `300`, or an inclusive range, e.g., `100-260`.
+/// Either end may be negative, e.g., `-16--1`.
+pub(crate) fn parse_position_range(s: &str) -> Result<RangeInclusive<isize>, String> {
+    let s = s.trim();
+    let parse = |p: &str| {
+        p.trim()
+            .parse::<isize>()
+            .map_err(|_| format!("'{s}' is not a position or range of positions"))
+    };
+
+    match s.get(1..).and_then(|rest| rest.find('-')) {
+        Some(k) => {
+            let (start, end) = (parse(&s[..=k])?, parse(&s[k + 2..])?);
+            if start > end {
+                return Err(format!("'{s}' has a start after its end"));
+            }
+            Ok(start..=end)
+        }
+        None => parse(s).map(|p| p..=p),
+    }
+}
+
+/// Reads positions and ranges of positions, one or more per line separated by
+/// commas or whitespace. Blank lines and `#` comments are skipped.
+pub(crate) fn read_position_ranges(path: &Path) -> Result<Vec<RangeInclusive<isize>>> {
+    let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+        kind: "positions file",
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            parse_position_range(field)
+                .map_err(|e| AadiffError::Invalid(format!("Invalid entry in positions file '{}': {e}", path.display())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions<'a>(
+        by_reference: bool, ref_codons: &'a [u8], offset: isize, labels: Option<HashMap<usize, String>>,
+        segments: &'a [Segment],
+    ) -> Positions<'a> {
+        // The reference "MK-V", its gap codon sharing K's number.
+        Positions {
+            by_reference,
+            ref_positions: vec![1, 2, 2, 3],
+            ref_codons,
+            offset,
+            labels,
+            segments,
+        }
+    }
+
+    #[test]
+    fn numbers_codons_by_alignment_or_reference() {
+        let codons = b"ATGAAA---GTT";
+        let by_alignment = positions(false, codons, -16, None, &[]);
+        assert_eq!(
+            (0..4).map(|i| by_alignment.numeric(i)).collect::<Vec<_>>(),
+            [-15, -14, -13, -12]
+        );
+        assert_eq!(by_alignment.nucleotide(9), 10);
+
+        let by_reference = positions(true, codons, 0, None, &[]);
+        assert_eq!(
+            (0..4).map(|i| by_reference.label(i)).collect::<Vec<_>>(),
+            ["1", "2", "2", "3"]
+        );
+        assert_eq!(by_reference.nucleotide(9), 7);
+    }
+
+    #[test]
+    fn labels_codons_within_segments() {
+        let segments = [
+            Segment {
+                name:   "HA".to_string(),
+                codons: 0..2,
+            },
+            Segment {
+                name:   "NA".to_string(),
+                codons: 2..4,
+            },
+        ];
+        let labels = HashMap::from([(1, "-16".to_string())]);
+        let numbered = positions(false, b"ATGAAA---GTT", 0, Some(labels), &segments);
+        assert!(numbered.has_genes());
+        assert_eq!(
+            (0..4).map(|i| numbered.label(i)).collect::<Vec<_>>(),
+            ["HA:-16", "HA:2", "NA:1", "NA:2"]
+        );
+
+        // Counting residues, NA's first follows the gap codon opening it.
+        let by_reference = positions(true, b"ATGAAA---GTT", 0, None, &segments);
+        assert_eq!([0, 1, 3].map(|i| by_reference.numeric(i)), [1, 2, 1]);
+    }
+
+    #[test]
+    fn parses_positions_and_ranges() {
+        assert_eq!(parse_position_range(" 300 "), Ok(300..=300));
+        assert_eq!(parse_position_range("100-260"), Ok(100..=260));
+        assert_eq!(parse_position_range("-16--1"), Ok(-16..=-1));
+        assert_eq!(parse_position_range("-5-3"), Ok(-5..=3));
+        assert!(parse_position_range("260-100").is_err());
+        assert!(parse_position_range("HA1").is_err());
+    }
+
+    #[test]
+    fn reads_numbering_files() {
+        let path = std::env::temp_dir().join(format!("aadiff-numbering-{}.tsv", std::process::id()));
+        std::fs::write(&path, "# H3 numbering\nalignment\tH3\n1\t-16\n\n17,1\n18 2\n").unwrap();
+        let numbering = read_numbering(&path).unwrap();
+        assert_eq!(
+            numbering,
+            HashMap::from([(1, "-16".to_string()), (17, "1".to_string()), (18, "2".to_string())])
+        );
+
+        std::fs::write(&path, "1\t-16\nx\t-15\n").unwrap();
+        assert!(matches!(read_numbering(&path), Err(AadiffError::Parse { line: 2, .. })));
+        std::fs::write(&path, "1\n").unwrap();
+        assert!(matches!(read_numbering(&path), Err(AadiffError::Parse { line: 1, .. })));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{
+    APDArgs, DeletionPolicy, DuplicatePolicy, NumberBy, ReferenceMode, RestrictMode,
+    error::{AadiffError, Result},
+    iter::DiffIter,
+    output::{Custom, Format, JsonSchema, OutputFormat},
+    translate::PartialCodonPolicy,
+};
+use clap::Parser;
+use std::{ops::RangeInclusive, path::PathBuf, sync::Mutex};
+
+/// Builds the options of a run, each setter meaning what the command-line flag of
+/// the same name does. Anything left unset keeps the command line's default.
+///
+/// ```no_run
+/// use aadiff::{DeletionPolicy, DiffOptions};
+///
+/// DiffOptions::new()
+///     .input("alignment.fasta")
+///     .output("differences.csv")
+///     .restrict_to_pairwise_alignable(true)
+///     .deletions(DeletionPolicy::Collapse)
+///     .run()?;
+/// # Ok::<(), aadiff::error::AadiffError>(())
+/// ```
+pub struct DiffOptions {
+    args: APDArgs,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions::new()
+    }
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        // Unlike the command line, a library stays quiet on stderr unless asked.
+        let mut args = APDArgs::parse_from(["aadiff", "--no-progress"]);
+        // Nor does it apply a config file, so none named by `AADIFF_CONFIG` is kept
+        // to be reported as used, e.g., by `--with-provenance`.
+        args.config = None;
+        DiffOptions { args }
+    }
+
+    /// The input FASTA; stdin if unset.
+    pub fn input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.input_fasta = Some(path.into());
+        self
+    }
+
+    /// The output file; stdout if unset.
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.output_xsv = Some(path.into());
+        self
+    }
+
+    /// Overwrites an existing output file, which is otherwise an error.
+    pub fn force(mut self, force: bool) -> Self {
+        self.args.force = force;
+        self
+    }
+
+    /// Adds rows to the end of an existing output file, without repeating its header.
+    pub fn append(mut self, append: bool) -> Self {
+        self.args.append = append;
+        self
+    }
+
+    pub fn output_format(mut self, format: Format) -> Self {
+        self.args.diff.output_format = format;
+        self
+    }
+
+    pub fn json_schema(mut self, schema: JsonSchema) -> Self {
+        self.args.diff.json_schema = schema;
+        self
+    }
+
+    pub fn json_metadata(mut self, metadata: bool) -> Self {
+        self.args.diff.json_metadata = metadata;
+        self
+    }
+
+    /// Renders the table with `formatter` in place of a built-in format.
+    pub fn formatter(mut self, formatter: impl OutputFormat + 'static) -> Self {
+        self.args.diff.formatter = Some(Custom(Mutex::new(Box::new(formatter))));
+        self
+    }
+
+    pub fn output_delimiter(mut self, delim: impl Into<String>) -> Self {
+        self.args.output_delimiter = Some(delim.into());
+        self
+    }
+
+    pub fn unix_line_endings(mut self, unix: bool) -> Self {
+        self.args.unix_line_endings = unix;
+        self
+    }
+
+    pub fn reference(mut self, reference: ReferenceMode) -> Self {
+        self.args.reference = reference;
+        self
+    }
+
+    pub fn align(mut self, align: bool) -> Self {
+        self.args.align = align;
+        self
+    }
+
+    pub fn low_memory(mut self, low_memory: bool) -> Self {
+        self.args.low_memory = low_memory;
+        self
+    }
+
+    pub fn segments(mut self, segments: bool) -> Self {
+        self.args.segments = segments;
+        self
+    }
+
+    pub fn restrict_to_pairwise_alignable(mut self, restrict: bool) -> Self {
+        self.args.restrict_to_pairwise_alignable = restrict;
+        self
+    }
+
+    pub fn restrict(mut self, mode: RestrictMode) -> Self {
+        self.args.restrict = Some(mode);
+        self
+    }
+
+    pub fn number_by(mut self, number_by: NumberBy) -> Self {
+        self.args.diff.number_by = number_by;
+        self
+    }
+
+    pub fn deletions(mut self, policy: DeletionPolicy) -> Self {
+        self.args.diff.deletions = policy;
+        self
+    }
+
+    pub fn partial_codon_policy(mut self, policy: PartialCodonPolicy) -> Self {
+        self.args.partial_codon_policy = policy;
+        self
+    }
+
+    pub fn strict_translation(mut self, strict: bool) -> Self {
+        self.args.strict_translation = strict;
+        self
+    }
+
+    pub fn respect_softmask(mut self, respect: bool) -> Self {
+        self.args.respect_softmask = respect;
+        self
+    }
+
+    pub fn on_duplicate(mut self, policy: DuplicatePolicy) -> Self {
+        self.args.on_duplicate = policy;
+        self
+    }
+
+    /// Sets the characters read as gaps, `.~?` by default.
+    pub fn gap_chars(mut self, chars: impl Into<String>) -> Self {
+        self.args.gap_chars = chars.into();
+        self
+    }
+
+    /// Reads the input as aligned amino acids rather than nucleotides.
+    pub fn protein(mut self, protein: bool) -> Self {
+        self.args.protein = protein;
+        self
+    }
+
+    pub fn degenerate_cap(mut self, cap: usize) -> Self {
+        self.args.diff.degenerate_cap = Some(cap);
+        self
+    }
+
+    pub fn ambiguous_matches_reference(mut self, matches: bool) -> Self {
+        self.args.diff.ambiguous_matches_reference = matches;
+        self
+    }
+
+    pub fn insertions(mut self, insertions: bool) -> Self {
+        self.args.diff.insertions = insertions;
+        self
+    }
+
+    pub fn position_offset(mut self, offset: isize) -> Self {
+        self.args.diff.position_offset = Some(offset);
+        self
+    }
+
+    pub fn first_position(mut self, position: isize) -> Self {
+        self.args.diff.first_position = Some(position);
+        self
+    }
+
+    pub fn numbering(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.diff.numbering = Some(path.into());
+        self
+    }
+
+    /// Adds to the positions reported; every position if none are added.
+    pub fn position(mut self, positions: RangeInclusive<isize>) -> Self {
+        self.args.diff.positions.push(positions);
+        self
+    }
+
+    pub fn mask(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.diff.mask = Some(path.into());
+        self
+    }
+
+    pub fn annotation(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.diff.annotation = Some(path.into());
+        self
+    }
+
+    pub fn watchlist(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.diff.watchlist = Some(path.into());
+        self
+    }
+
+    pub fn ancestor(mut self, name: impl Into<String>) -> Self {
+        self.args.diff.ancestor = Some(name.into());
+        self
+    }
+
+    /// Adds a record to also diff every query against; may be called repeatedly.
+    pub fn extra_reference(mut self, name: impl Into<String>) -> Self {
+        self.args.diff.extra_references.push(name.into());
+        self
+    }
+
+    pub fn max_ambiguity(mut self, fraction: f64) -> Self {
+        self.args.max_ambiguity = Some(fraction);
+        self
+    }
+
+    pub fn max_gaps(mut self, fraction: f64) -> Self {
+        self.args.max_gaps = Some(fraction);
+        self
+    }
+
+    pub fn min_count(mut self, count: usize) -> Self {
+        self.args.diff.min_count = Some(count);
+        self
+    }
+
+    pub fn min_freq(mut self, fraction: f64) -> Self {
+        self.args.diff.min_freq = Some(fraction);
+        self
+    }
+
+    pub fn synonymous(mut self, synonymous: bool) -> Self {
+        self.args.diff.synonymous = synonymous;
+        self
+    }
+
+    pub fn identity(mut self, identity: bool) -> Self {
+        self.args.diff.identity = identity;
+        self
+    }
+
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.args.diff.stats = stats;
+        self
+    }
+
+    pub fn cluster(mut self, cluster: bool) -> Self {
+        self.args.diff.cluster = cluster;
+        self
+    }
+
+    pub fn groups(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.diff.groups = Some(path.into());
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.args.threads = Some(threads);
+        self
+    }
+
+    /// Shows progress bars on stderr when it is a terminal; off by default.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.args.no_progress = !progress;
+        self
+    }
+
+    /// Checks the options as the command line would: that fractions are in range, and
+    /// that no two options are set that conflict there, e.g., `force` and `append`.
+    /// Gives the arguments to pass to [`run`](crate::run).
+    pub fn build(self) -> Result<APDArgs> {
+        let args = self.args;
+        let diff = &args.diff;
+        for (first, second, set) in [
+            ("force", "append", args.force && args.append),
+            (
+                "position-offset",
+                "first-position",
+                diff.position_offset.is_some() && diff.first_position.is_some(),
+            ),
+            ("low-memory", "align", args.low_memory && args.align),
+            ("low-memory", "segments", args.low_memory && args.segments),
+            ("cluster", "groups", diff.cluster && diff.groups.is_some()),
+            (
+                "cluster",
+                "extra-reference",
+                diff.cluster && !diff.extra_references.is_empty(),
+            ),
+        ] {
+            if set {
+                return Err(AadiffError::Invalid(format!("--{first} cannot be used with --{second}")));
+            }
+        }
+        for (flag, fraction) in [
+            ("max-ambiguity", args.max_ambiguity),
+            ("max-gaps", args.max_gaps),
+            ("min-freq", args.diff.min_freq),
+        ] {
+            if let Some(fraction) = fraction
+                && !(fraction > 0.0 && fraction <= 1.0)
+            {
+                return Err(AadiffError::Invalid(format!(
+                    "--{flag} {fraction} is not a fraction in (0, 1]"
+                )));
+            }
+        }
+        Ok(args)
+    }
+
+    /// Builds the options and runs `aadiff` with them.
+    pub fn run(self) -> Result<()> {
+        crate::run(self.build()?)
+    }
+
+    /// Builds the options and iterates over the rows of the difference table, which
+    /// is not written, though any `--output-csv`, `--output-json`, or
+    /// `--output-summary` files are.
+    pub fn iter(self) -> Result<DiffIter> {
+        DiffIter::new(self.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_conflicting_options() {
+        assert!(DiffOptions::new().force(true).append(true).build().is_err());
+        assert!(DiffOptions::new().position_offset(10).first_position(11).build().is_err());
+        assert!(DiffOptions::new().low_memory(true).align(true).build().is_err());
+        assert!(DiffOptions::new().cluster(true).groups("clades.tsv").build().is_err());
+        assert!(DiffOptions::new().low_memory(true).cluster(true).build().is_ok());
+    }
+}
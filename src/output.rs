@@ -0,0 +1,453 @@
+mod csv;
+mod json;
+mod summary;
+mod terminal;
+
+pub use csv::Csv;
+pub(crate) use csv::push_field;
+pub use json::{Json, JsonSchema};
+pub use summary::Summary;
+pub(crate) use terminal::CellKind;
+pub use terminal::Terminal;
+
+use crate::{
+    APDArgs,
+    error::Result,
+    open_output,
+    table::{CellValue, DiffTable, PositionDiff},
+};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::{Mutex, mpsc::SyncSender},
+};
+
+/// Renders the difference table as it is computed. Every row has a label (the
+/// position, or the name of a per-sequence summary), the reference cell, one
+/// optional cell per query, and any extra annotation columns; `table` holds the
+/// column names but no rows.
+pub trait OutputFormat: Send {
+    /// Writes anything that comes before the first row.
+    fn header(&mut self, _writer: &mut dyn Write, _table: &DiffTable) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn row(&mut self, writer: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()>;
+
+    /// Writes anything that comes after the last row.
+    fn footer(&mut self, _writer: &mut dyn Write, _table: &DiffTable) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The built-in formats of the difference table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    #[default]
+    Csv,
+    Json,
+    /// Plain text counting the positions that differ and the differences of each
+    /// query
+    Summary,
+}
+
+impl Format {
+    /// The formatter for this format, configured by the output flags of `args`.
+    pub fn formatter(self, args: &APDArgs) -> Result<Box<dyn OutputFormat>> {
+        let line_ending = if args.unix_line_endings { "" } else { "\r" };
+        Ok(match self {
+            Format::Csv => {
+                let csv = Csv::new(args.output_delimiter.as_deref().unwrap_or(","), line_ending);
+                Box::new(match args.provenance()? {
+                    Some(provenance) => csv.with_comments(provenance.comment_lines(line_ending)),
+                    None => csv,
+                })
+            }
+            Format::Json => {
+                let json = Json::new(args.diff.json_schema);
+                Box::new(if args.diff.json_metadata || args.diff.with_provenance {
+                    json.with_metadata(args)?
+                } else {
+                    json
+                })
+            }
+            Format::Summary => Box::new(Summary::default()),
+        })
+    }
+}
+
+/// A formatter supplied in place of a built-in one, behind a lock so that the
+/// arguments holding it can be shared between threads.
+pub(crate) struct Custom(pub(crate) Mutex<Box<dyn OutputFormat>>);
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Custom")
+    }
+}
+
+/// Writes the difference table through several formats in one pass, each to its
+/// own file as well as, if there is one, the main output.
+pub(crate) struct Tee {
+    main: Option<Box<dyn OutputFormat>>,
+    also: Vec<(Box<dyn OutputFormat>, BufWriter<File>)>,
+}
+
+impl Tee {
+    pub(crate) fn new(main: Option<Box<dyn OutputFormat>>, also: Vec<(Box<dyn OutputFormat>, BufWriter<File>)>) -> Self {
+        Tee { main, also }
+    }
+}
+
+impl OutputFormat for Tee {
+    fn header(&mut self, writer: &mut dyn Write, table: &DiffTable) -> io::Result<()> {
+        if let Some(main) = &mut self.main {
+            main.header(writer, table)?;
+        }
+        for (format, file) in &mut self.also {
+            format.header(file, table)?;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, writer: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        if let Some(main) = &mut self.main {
+            main.row(writer, table, row)?;
+        }
+        for (format, file) in &mut self.also {
+            format.row(file, table, row)?;
+        }
+        Ok(())
+    }
+
+    fn footer(&mut self, writer: &mut dyn Write, table: &DiffTable) -> io::Result<()> {
+        if let Some(main) = &mut self.main {
+            main.footer(writer, table)?;
+        }
+        for (format, file) in &mut self.also {
+            format.footer(file, table)?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the difference table through an [`OutputFormat`] one row at a time.
+///
+/// When collapsing, rows are held in the table until the end so that queries whose
+/// cells are identical in every row can be written as a single column.
+pub(crate) struct TableWriter<W: Write> {
+    writer:   W,
+    format:   Box<dyn OutputFormat>,
+    table:    DiffTable,
+    collapse: bool,
+    sender:   Option<SyncSender<TableEvent>>,
+}
+
+/// The parts of a table sent to another thread in place of being written.
+pub(crate) enum TableEvent {
+    Header { columns: Vec<String>, extras: Vec<String> },
+    Row(PositionDiff),
+}
+
+impl<W: Write> TableWriter<W> {
+    pub(crate) fn new(writer: W, format: Box<dyn OutputFormat>) -> Self {
+        TableWriter {
+            writer,
+            format,
+            table:    DiffTable::default(),
+            collapse: false,
+            sender:   None,
+        }
+    }
+
+    /// Opens the table as asked for by `args`: in `formatter`, on the terminal, or
+    /// in the chosen format, along with any other outputs, and collapsed or sent
+    /// to `sender` if asked to.
+    pub(crate) fn open(
+        writer: W, args: &APDArgs, formatter: Option<Custom>, to_terminal: bool, sender: Option<SyncSender<TableEvent>>,
+    ) -> Result<Self> {
+        let format = match formatter {
+            Some(Custom(format)) => format.into_inner().unwrap_or_else(|e| e.into_inner()),
+            None if args.diff.color && args.diff.output_format == Format::Csv && to_terminal => {
+                Box::new(Terminal::default())
+            }
+            None => args.diff.output_format.formatter(args)?,
+        };
+        let mut also = Vec::new();
+        for (format, path) in [
+            (Format::Csv, &args.diff.output_csv),
+            (Format::Json, &args.diff.output_json),
+            (Format::Summary, &args.diff.output_summary),
+        ] {
+            if let Some(path) = path {
+                also.push((
+                    format.formatter(args)?,
+                    BufWriter::new(open_output(path, args.force || args.rerun, false)?),
+                ));
+            }
+        }
+        // Given other outputs, the table only goes to the main one if it is named. Sent
+        // to another thread, it only goes to the other outputs.
+        let format = match (also.is_empty(), &args.output_xsv) {
+            _ if sender.is_some() => Box::new(Tee::new(None, also)),
+            (true, _) => format,
+            (false, Some(_)) => Box::new(Tee::new(Some(format), also)),
+            (false, None) => Box::new(Tee::new(None, also)),
+        };
+        let mut table = TableWriter::new(writer, format);
+        if args.diff.cluster {
+            table = table.collapse_identical();
+        }
+        if let Some(sender) = sender {
+            table = table.send_to(sender);
+        }
+        Ok(table)
+    }
+
+    /// Sends the header and rows through a channel as well as through the format,
+    /// which then only writes any other outputs. Once the receiver hangs up, writing
+    /// fails with [`io::ErrorKind::BrokenPipe`], stopping the run.
+    pub(crate) fn send_to(mut self, sender: SyncSender<TableEvent>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Collapses queries with identical cells in every row into one column per
+    /// cluster, named for its first member and described by trailing `cluster`,
+    /// `cluster_size`, and `members` rows.
+    pub(crate) fn collapse_identical(mut self) -> Self {
+        self.collapse = true;
+        self
+    }
+
+    /// Sets the sequence column names, reference first, followed by the names of any
+    /// extra columns, and writes the header.
+    pub(crate) fn header(&mut self, columns: Vec<String>, extras: Vec<String>) -> io::Result<()> {
+        self.table.columns = columns;
+        self.table.extras = extras;
+        if self.collapse {
+            return Ok(());
+        }
+        self.emit_header()
+    }
+
+    /// Writes a row, or holds it when collapsing.
+    pub(crate) fn row(&mut self, row: PositionDiff) -> io::Result<()> {
+        if self.collapse {
+            self.table.rows.push(row);
+            return Ok(());
+        }
+        self.emit(row)
+    }
+
+    fn emit_header(&mut self) -> io::Result<()> {
+        self.send(TableEvent::Header {
+            columns: self.table.columns.clone(),
+            extras:  self.table.extras.clone(),
+        })?;
+        self.format.header(&mut self.writer, &self.table)
+    }
+
+    fn emit(&mut self, row: PositionDiff) -> io::Result<()> {
+        self.format.row(&mut self.writer, &self.table, &row)?;
+        self.send(TableEvent::Row(row))
+    }
+
+    /// Sends a part of the table to the receiver, if any, failing once it has hung up.
+    fn send(&self, event: TableEvent) -> io::Result<()> {
+        match &self.sender {
+            Some(sender) => sender
+                .send(event)
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the receiver of the table hung up")),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes anything the format holds until the end and flushes the output.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        if self.collapse {
+            let rows = std::mem::take(&mut self.table.rows);
+            self.write_collapsed(rows)?;
+        }
+
+        self.format.footer(&mut self.writer, &self.table)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_collapsed(&mut self, held: Vec<PositionDiff>) -> io::Result<()> {
+        let queries = self.table.columns.len().saturating_sub(1);
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        let mut by_profile: HashMap<Vec<&CellValue>, usize> = HashMap::new();
+        for query in 0..queries {
+            let profile = held.iter().map(|row| &row.cells[query]).collect::<Vec<_>>();
+            match by_profile.get(&profile) {
+                Some(&cluster) => clusters[cluster].push(query),
+                None => {
+                    by_profile.insert(profile, clusters.len());
+                    clusters.push(vec![query]);
+                }
+            }
+        }
+
+        let names = std::mem::take(&mut self.table.columns);
+        let representatives = clusters.iter().map(|members| members[0]).collect::<Vec<_>>();
+        self.table.columns = std::iter::once(names[0].clone())
+            .chain(representatives.iter().map(|&q| names[q + 1].clone()))
+            .collect();
+        self.emit_header()?;
+
+        for row in &held {
+            let pick = |cells: &[CellValue]| representatives.iter().map(|&q| cells[q].clone()).collect::<Vec<_>>();
+            self.emit(PositionDiff {
+                label:     row.label.clone(),
+                reference: row.reference.clone(),
+                cells:     pick(&row.cells),
+                extras:    row.extras.clone(),
+                notes:     row.notes.iter().map(|(key, notes)| (key.clone(), pick(notes))).collect(),
+            })?;
+        }
+
+        let ids = (1..=clusters.len()).map(|c| Some(format!("C{c}"))).collect();
+        self.emit(PositionDiff::new("cluster", "", ids, Vec::new()))?;
+        let sizes = clusters.iter().map(|m| Some(m.len().to_string())).collect();
+        self.emit(PositionDiff::new("cluster_size", "", sizes, Vec::new()))?;
+        let members = clusters
+            .iter()
+            .map(|m| Some(m.iter().map(|&q| names[q + 1].as_str()).collect::<Vec<_>>().join(";")))
+            .collect();
+        self.emit(PositionDiff::new("members", "", members, Vec::new()))
+    }
+}
+
+/// Drops the header written, along with any `#` comments before it, when adding rows
+/// to an output that already has one, and refuses to add them under a header that
+/// differs from it.
+pub(crate) struct SkipHeader<W: Write> {
+    writer: W,
+    /// The header of the output added to, while the new one is still being skipped
+    existing: Option<Vec<u8>>,
+    pending:  Vec<u8>,
+}
+
+impl<W: Write> SkipHeader<W> {
+    pub(crate) fn new(writer: W, existing: Option<Vec<u8>>) -> Self {
+        SkipHeader {
+            writer,
+            existing,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for SkipHeader<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(existing) = &self.existing else {
+            return self.writer.write(buf);
+        };
+        self.pending.extend_from_slice(buf);
+        if let Some(header) = header_record(&self.pending) {
+            if self.pending[header.clone()].trim_ascii_end() != existing.as_slice() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the columns differ from those of the file appended to",
+                ));
+            }
+            self.writer.write_all(&self.pending[header.end..])?;
+            self.existing = None;
+            self.pending = Vec::new();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// The header of a delimited table, i.e., its first record after any `#` comment
+/// lines, which spans several lines where a quoted name holds a line break. Gives
+/// its bytes without the line ending, or nothing if there is none.
+pub(crate) fn read_header(reader: impl io::BufRead) -> io::Result<Vec<u8>> {
+    let mut text = Vec::new();
+    for line in reader.split(b'\n') {
+        text.extend(line?);
+        text.push(b'\n');
+        if let Some(header) = header_record(&text) {
+            return Ok(text[header].trim_ascii_end().to_vec());
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Where the header record of a delimited table is in the text written so far, if
+/// it is complete, after any `#` comment lines.
+fn header_record(text: &[u8]) -> Option<std::ops::Range<usize>> {
+    let (mut start, mut line_start, mut quotes) = (0, 0, 0);
+    for (i, _) in text.iter().enumerate().filter(|&(_, &b)| b == b'\n') {
+        let line = &text[line_start..=i];
+        line_start = i + 1;
+        if start + line.len() == line_start && line.starts_with(b"#") {
+            start = line_start;
+            continue;
+        }
+        quotes += line.iter().filter(|&&b| b == b'"').count();
+        if quotes % 2 == 0 {
+            return Some(start..line_start);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append(existing: &[u8], pieces: &[&[u8]]) -> io::Result<Vec<u8>> {
+        let mut writer = SkipHeader::new(Vec::new(), Some(existing.to_vec()));
+        for piece in pieces {
+            writer.write_all(piece)?;
+        }
+        Ok(writer.writer)
+    }
+
+    #[test]
+    fn skips_the_header_when_appending() {
+        // The header may arrive in pieces, and its line ending is not compared.
+        let rows = append(b"position,ref,q1", &[b"position,re", b"f,q1\r\n1,A,V\r\n", b"2,K,R\r\n"]).unwrap();
+        assert_eq!(rows, b"1,A,V\r\n2,K,R\r\n");
+        // Provenance comments before it go with it.
+        let rows = append(
+            b"position,ref,q1",
+            &[b"# aadiff 0.3.0\n# command: aadiff\nposition,ref,q1\n3,S,-\n"],
+        )
+        .unwrap();
+        assert_eq!(rows, b"3,S,-\n");
+        // A quoted name holding a line break stays within the header.
+        let rows = append(b"position,ref,\"q\n1\"", &[b"position,ref,\"q\n1\"\n4,G,E\n"]).unwrap();
+        assert_eq!(rows, b"4,G,E\n");
+
+        let mut writer = SkipHeader::new(Vec::new(), None);
+        writer.write_all(b"position,ref\n1,A\n").unwrap();
+        assert_eq!(writer.writer, b"position,ref\n1,A\n");
+    }
+
+    #[test]
+    fn refuses_to_append_other_columns() {
+        let error = append(b"position,ref,q1", &[b"position,ref,q2\n1,A,V\n"]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reads_the_header_after_comments() {
+        let header = read_header(&b"# aadiff 0.3.0\r\nposition,ref,\"q\n1\"\r\n1,A,V\r\n"[..]).unwrap();
+        assert_eq!(header, b"position,ref,\"q\n1\"");
+        assert!(read_header(&b""[..]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use super::OutputFormat;
+use crate::table::{DiffTable, PositionDiff};
+use std::io::{self, Write};
+
+/// A delimited table with a row per position and a column per sequence, each query
+/// cell quoted. Names and labels are quoted only when they need to be.
+pub struct Csv {
+    delim:       String,
+    line_ending: &'static str,
+    comments:    String,
+    buffer:      String,
+}
+
+impl Csv {
+    /// The delimiter may be any string, e.g., `'\t'` or `" | "`.
+    pub fn new(delim: impl Into<String>, line_ending: &'static str) -> Self {
+        Csv {
+            delim: delim.into(),
+            line_ending,
+            comments: String::new(),
+            buffer:   String::new(),
+        }
+    }
+
+    /// Writes lines before the header, each starting with `#`, e.g., the provenance
+    /// of the table.
+    pub fn with_comments(mut self, comments: String) -> Self {
+        self.comments = comments;
+        self
+    }
+}
+
+impl OutputFormat for Csv {
+    fn header(&mut self, writer: &mut dyn Write, table: &DiffTable) -> io::Result<()> {
+        writer.write_all(self.comments.as_bytes())?;
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        for column in table.columns.iter().chain(&table.extras) {
+            buffer.push_str(&self.delim);
+            push_field(buffer, column, &self.delim);
+        }
+        writeln!(writer, "{buffer}{}", self.line_ending)
+    }
+
+    /// Writes through a buffer reused from row to row, so that large tables are not
+    /// slowed down by an allocation per cell.
+    fn row(&mut self, writer: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        let mut fields = table.fields(row);
+        push_field(buffer, &row.label, &self.delim);
+        if let Some((_, reference)) = fields.next() {
+            buffer.push_str(&self.delim);
+            push_field(buffer, reference.as_str().unwrap_or_default(), &self.delim);
+        }
+        for (_, cell) in fields {
+            buffer.push_str(&self.delim);
+            if let Some(cell) = cell.as_str() {
+                push_quoted(buffer, cell);
+            }
+        }
+        buffer.push_str(self.line_ending);
+        buffer.push('\n');
+        writer.write_all(buffer.as_bytes())
+    }
+}
+
+/// Adds a field, such as a name or a label from a user's file, quoted only when it
+/// would otherwise split or end the line.
+pub(crate) fn push_field(buffer: &mut String, field: &str, delim: &str) {
+    if (!delim.is_empty() && field.contains(delim)) || field.contains(['"', '\r', '\n']) {
+        push_quoted(buffer, field);
+    } else {
+        buffer.push_str(field);
+    }
+}
+
+/// Adds a field in quotes, doubling any quotes within it.
+fn push_quoted(buffer: &mut String, field: &str) {
+    buffer.push('"');
+    if field.contains('"') {
+        buffer.push_str(&field.replace('"', "\"\""));
+    } else {
+        buffer.push_str(field);
+    }
+    buffer.push('"');
+}
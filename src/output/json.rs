@@ -0,0 +1,210 @@
+use super::OutputFormat;
+use crate::{
+    APDArgs,
+    error::{AadiffError, Result},
+    provenance::Provenance,
+    table::{DiffTable, PositionDiff},
+};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// The version of the JSON document written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonSchema {
+    /// An object keyed by row label, each row an object of its non-empty cells keyed
+    /// by column
+    V1,
+    /// An envelope holding the schema version, the reference and column names, any
+    /// metadata, and the rows as `positions`
+    #[default]
+    V2,
+    /// An array of the rows of v1, in table order, each with its label as
+    /// `position`, a number where it is one
+    Records,
+}
+
+/// The difference table as JSON. Only JSON has room for the notes on each query
+/// cell.
+pub struct Json {
+    schema:   JsonSchema,
+    metadata: Option<String>,
+    rows:     usize,
+    buffer:   String,
+}
+
+/// What produced a schema v2 document.
+#[derive(Serialize)]
+struct Metadata<'a> {
+    tool:       &'static str,
+    version:    &'static str,
+    parameters: &'a APDArgs,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    provenance: Option<&'a Provenance>,
+}
+
+impl Json {
+    pub fn new(schema: JsonSchema) -> Self {
+        Json {
+            schema,
+            metadata: None,
+            rows:     0,
+            buffer:   String::new(),
+        }
+    }
+
+    /// Embeds the aadiff version and the parameters of the run in a schema v2
+    /// document, along with its provenance given `--with-provenance`.
+    pub fn with_metadata(mut self, args: &APDArgs) -> Result<Self> {
+        let metadata = Metadata {
+            tool:       env!("CARGO_PKG_NAME"),
+            version:    env!("CARGO_PKG_VERSION"),
+            parameters: args,
+            provenance: args.provenance()?,
+        };
+        self.metadata = Some(serde_json::to_string(&metadata).map_err(|e| AadiffError::Output(e.into()))?);
+        Ok(self)
+    }
+
+    /// Writes a row in schema v1, as an entry keyed by label whose notes go in an
+    /// object per set keyed by query.
+    fn row_v1(&mut self, writer: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        // Rows are written as they come, so only the object around them is left for
+        // the end.
+        buffer.push_str(if self.rows == 0 { "{\n  " } else { ",\n  " });
+        self.rows += 1;
+        push_json_string(buffer, &row.label);
+        buffer.push_str(":{");
+        push_cells(buffer, table, row, "");
+        buffer.push('}');
+        writer.write_all(buffer.as_bytes())
+    }
+
+    /// Writes a row as an element of the records array: the object of v1, led by
+    /// its `position`.
+    fn row_records(&mut self, writer: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        buffer.push_str(if self.rows == 0 { "[\n  " } else { ",\n  " });
+        self.rows += 1;
+        buffer.push_str("{\"position\":");
+        match row.label.parse::<i64>() {
+            Ok(position) => {
+                let _ = write!(buffer, "{position}");
+            }
+            Err(_) => push_json_string(buffer, &row.label),
+        }
+        push_cells(buffer, table, row, ",");
+        buffer.push('}');
+        writer.write_all(buffer.as_bytes())
+    }
+}
+
+/// Writes the non-empty cells of a row keyed by column, and its notes in an object
+/// per set keyed by query, the first after `separator`.
+fn push_cells(buffer: &mut String, table: &DiffTable, row: &PositionDiff, mut separator: &str) {
+    for (column, cell) in table.fields(row) {
+        if let Some(cell) = cell.as_str() {
+            buffer.push_str(separator);
+            separator = ",";
+            push_json_pair(buffer, column, cell);
+        }
+    }
+
+    for (key, notes) in &row.notes {
+        let mut notes = table.columns[1..]
+            .iter()
+            .zip(notes)
+            .filter_map(|(column, note)| Some((column, note.as_str()?)))
+            .peekable();
+        if notes.peek().is_none() {
+            continue;
+        }
+
+        buffer.push_str(separator);
+        separator = ",";
+        push_json_string(buffer, key);
+        buffer.push_str(":{");
+        for (n, (column, note)) in notes.enumerate() {
+            if n > 0 {
+                buffer.push(',');
+            }
+            push_json_pair(buffer, column, note);
+        }
+        buffer.push('}');
+    }
+}
+
+impl OutputFormat for Json {
+    fn header(&mut self, writer: &mut dyn Write, table: &DiffTable) -> io::Result<()> {
+        if self.schema != JsonSchema::V2 {
+            return Ok(());
+        }
+
+        write!(writer, "{{\"schema_version\":2,\"reference\":")?;
+        serde_json::to_writer(&mut *writer, table.columns.first().map_or("", String::as_str))?;
+        write!(writer, ",\"columns\":")?;
+        serde_json::to_writer(&mut *writer, &table.columns)?;
+        write!(writer, ",\"extras\":")?;
+        serde_json::to_writer(&mut *writer, &table.extras)?;
+        if let Some(metadata) = &self.metadata {
+            write!(writer, ",\"metadata\":{metadata}")?;
+        }
+        write!(writer, ",\"positions\":[")
+    }
+
+    fn row(&mut self, writer: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        match self.schema {
+            JsonSchema::V1 => return self.row_v1(writer, table, row),
+            JsonSchema::Records => return self.row_records(writer, table, row),
+            JsonSchema::V2 => {}
+        }
+
+        writer.write_all(if self.rows == 0 { b"\n  " } else { b",\n  " })?;
+        self.rows += 1;
+        serde_json::to_writer(&mut *writer, row)?;
+        Ok(())
+    }
+
+    fn footer(&mut self, writer: &mut dyn Write, _: &DiffTable) -> io::Result<()> {
+        match (self.schema, self.rows) {
+            (JsonSchema::V1, 0) => writeln!(writer, "{{}}"),
+            (JsonSchema::V1, _) => writeln!(writer, "\n}}"),
+            (JsonSchema::Records, 0) => writeln!(writer, "[]"),
+            (JsonSchema::Records, _) => writeln!(writer, "\n]"),
+            (JsonSchema::V2, 0) => writeln!(writer, "]}}"),
+            (JsonSchema::V2, _) => writeln!(writer, "\n]}}"),
+        }
+    }
+}
+
+fn push_json_pair(json: &mut String, key: &str, value: &str) {
+    push_json_string(json, key);
+    json.push(':');
+    push_json_string(json, value);
+}
+
+fn push_json_string(json: &mut String, s: &str) {
+    json.reserve(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
@@ -0,0 +1,68 @@
+use super::OutputFormat;
+use crate::{
+    names::printable_name,
+    table::{CellValue, DiffTable, PositionDiff},
+};
+use std::io::{self, Write};
+
+/// A plain-text summary of the difference table, written once every row is in: the
+/// number of positions where any query differs, and the differences of each query.
+/// Only substitutions, deletions, and insertions count as differences, not
+/// synonymous changes, masked or frameshifted codons, or missing and ambiguous residues.
+#[derive(Debug, Default)]
+pub struct Summary {
+    positions:   usize,
+    differences: Vec<usize>,
+}
+
+impl OutputFormat for Summary {
+    fn row(&mut self, _: &mut dyn Write, _: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        self.differences.resize(row.cells.len(), 0);
+        // Per-sequence summaries, e.g., `identity`, have no reference cell, though
+        // each insertion listed in the `insertions` row counts.
+        if row.reference == CellValue::Empty {
+            if row.label == "insertions" {
+                for (count, cell) in self.differences.iter_mut().zip(&row.cells) {
+                    *count += cell.as_str().map_or(0, |cell| cell.split(';').count());
+                }
+            }
+            return Ok(());
+        }
+
+        let mut differs = false;
+        for (count, cell) in self.differences.iter_mut().zip(&row.cells) {
+            if cell.as_str().is_some_and(is_difference) {
+                *count += 1;
+                differs = true;
+            }
+        }
+        self.positions += usize::from(differs);
+        Ok(())
+    }
+
+    fn footer(&mut self, writer: &mut dyn Write, table: &DiffTable) -> io::Result<()> {
+        let queries = table.columns.get(1..).unwrap_or_default();
+        let reference = table.columns.first().map(|name| printable_name(name)).unwrap_or_default();
+        writeln!(writer, "Reference: {reference}")?;
+        writeln!(writer, "Queries: {}", queries.len())?;
+        writeln!(writer, "Positions with differences: {}", self.positions)?;
+        writeln!(writer)?;
+        writeln!(writer, "Differences per query:")?;
+        for (n, query) in queries.iter().enumerate() {
+            writeln!(
+                writer,
+                "{}\t{}",
+                printable_name(query),
+                self.differences.get(n).copied().unwrap_or(0)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a query cell reports a substitution or deletion, ignoring the marks
+/// appended for watched mutations, reversions, and private mutations.
+fn is_difference(cell: &str) -> bool {
+    let cell = cell.trim_end_matches(['!', '<', '^']);
+    !matches!(cell, "" | "syn" | "fs" | "masked" | "missing") && !cell.contains('/') && !cell.starts_with('X')
+}
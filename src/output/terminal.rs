@@ -0,0 +1,93 @@
+use super::OutputFormat;
+use crate::{
+    names::printable_name,
+    table::{DiffTable, PositionDiff},
+};
+use std::io::{self, Write};
+
+/// The difference table as aligned columns to read in a terminal, with query cells
+/// colored by kind: substitutions red, deletions blue, ambiguities yellow, and masked
+/// or frameshifted codons dim. Rows are held until the end to size the columns.
+#[derive(Debug, Default)]
+pub struct Terminal {
+    rows: Vec<Vec<String>>,
+}
+
+/// What a query cell reports, for coloring it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CellKind {
+    Substitution,
+    Deletion,
+    Ambiguity,
+    /// A masked or frameshifted codon, which is not compared.
+    Uncompared,
+}
+
+impl CellKind {
+    /// The kind of a query cell, if it is colored, ignoring the markers after it.
+    pub(crate) fn of(cell: &str) -> Option<Self> {
+        let cell = cell.trim_end_matches(['!', '<', '^']);
+        match cell {
+            "" | "syn" => None,
+            "masked" | "fs" => Some(CellKind::Uncompared),
+            _ if cell.starts_with("del") => Some(CellKind::Deletion),
+            _ if cell.contains('/') || cell.starts_with('X') || cell == "missing" => Some(CellKind::Ambiguity),
+            _ => Some(CellKind::Substitution),
+        }
+    }
+
+    /// The ANSI color code: red, blue, yellow, or dim.
+    fn ansi(self) -> &'static str {
+        match self {
+            CellKind::Substitution => "31",
+            CellKind::Deletion => "34",
+            CellKind::Ambiguity => "33",
+            CellKind::Uncompared => "2",
+        }
+    }
+}
+
+impl OutputFormat for Terminal {
+    fn row(&mut self, _: &mut dyn Write, table: &DiffTable, row: &PositionDiff) -> io::Result<()> {
+        let cells = table
+            .fields(row)
+            .map(|(_, cell)| cell.as_str().unwrap_or_default().to_string());
+        self.rows.push(std::iter::once(row.label.clone()).chain(cells).collect());
+        Ok(())
+    }
+
+    fn footer(&mut self, writer: &mut dyn Write, table: &DiffTable) -> io::Result<()> {
+        let header = std::iter::once(String::new())
+            .chain(table.columns.iter().chain(&table.extras).map(|name| printable_name(name)))
+            .collect::<Vec<_>>();
+        let mut widths = vec![0; header.len()];
+        for row in std::iter::once(&header).chain(&self.rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        // The label and reference come first, then the queries, then any extras.
+        let queries = 2..table.columns.len() + 1;
+        writeln!(writer, "\x1b[1m{}\x1b[0m", pad(&header, &widths).join("  "))?;
+        for row in &self.rows {
+            let cells = pad(row, &widths)
+                .into_iter()
+                .enumerate()
+                .map(|(k, cell)| match CellKind::of(&row[k]).filter(|_| queries.contains(&k)) {
+                    Some(kind) => format!("\x1b[{}m{cell}\x1b[0m", kind.ansi()),
+                    None => cell,
+                })
+                .collect::<Vec<_>>();
+            writeln!(writer, "{}", cells.join("  "))?;
+        }
+        Ok(())
+    }
+}
+
+fn pad(row: &[String], widths: &[usize]) -> Vec<String> {
+    row.iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{cell:width$}"))
+        .collect()
+}
@@ -0,0 +1,47 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+
+/// A progress bar on stderr, only drawn when stderr is a terminal and progress was
+/// not turned off with `--no-progress`.
+pub(crate) struct Progress(ProgressBar);
+
+impl Progress {
+    /// Counts records as they are read.
+    pub(crate) fn records(show: bool) -> Self {
+        let bar = if drawn(show) {
+            ProgressBar::new_spinner()
+        } else {
+            ProgressBar::hidden()
+        };
+        bar.set_style(style("{spinner} Reading records: {human_pos} [{elapsed}]"));
+        Progress(bar)
+    }
+
+    /// Counts the positions of the reference as they are diffed.
+    pub(crate) fn positions(show: bool, len: usize) -> Self {
+        let bar = if drawn(show) {
+            ProgressBar::new(len as u64)
+        } else {
+            ProgressBar::hidden()
+        };
+        bar.set_style(style("Diffing positions {wide_bar} {pos}/{len} [{elapsed}<{eta}]"));
+        Progress(bar)
+    }
+
+    pub(crate) fn inc(&self) {
+        self.0.inc(1);
+    }
+
+    /// Clears the bar, so that it does not linger above later messages.
+    pub(crate) fn finish(&self) {
+        self.0.finish_and_clear();
+    }
+}
+
+fn drawn(show: bool) -> bool {
+    show && std::io::stderr().is_terminal()
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template).unwrap_or_else(|_| ProgressStyle::default_bar())
+}
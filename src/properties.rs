@@ -0,0 +1,41 @@
+/// Broad physicochemical class of an amino acid.
+fn class(aa: u8) -> Option<&'static str> {
+    match aa {
+        b'A' | b'V' | b'L' | b'I' | b'M' | b'F' | b'W' => Some("hydrophobic"),
+        b'S' | b'T' | b'N' | b'Q' | b'Y' | b'C' => Some("polar"),
+        b'K' | b'R' | b'H' => Some("positive"),
+        b'D' | b'E' => Some("negative"),
+        b'G' | b'P' => Some("special"),
+        _ => None,
+    }
+}
+
+/// Side chain size class of an amino acid, from smallest to largest.
+fn size(aa: u8) -> Option<u8> {
+    match aa {
+        b'G' | b'A' | b'S' | b'C' | b'T' | b'P' | b'D' | b'N' => Some(0),
+        b'V' | b'E' | b'Q' | b'H' | b'I' | b'L' | b'M' | b'K' => Some(1),
+        b'F' | b'R' | b'W' | b'Y' => Some(2),
+        _ => None,
+    }
+}
+
+/// Describes the physicochemical change of a substitution, e.g., `hydrophobic->positive,
+/// larger`, or only the shared class when it is kept, e.g., `polar`. Anything other
+/// than a substitution between standard amino acids has no description.
+pub(crate) fn property_change(from: u8, to: u8) -> Option<String> {
+    let (from_class, to_class) = (class(from)?, class(to)?);
+    let (from_size, to_size) = (size(from)?, size(to)?);
+
+    let mut change = if from_class == to_class {
+        from_class.to_string()
+    } else {
+        format!("{from_class}->{to_class}")
+    };
+    if to_size > from_size {
+        change.push_str(", larger");
+    } else if to_size < from_size {
+        change.push_str(", smaller");
+    }
+    Some(change)
+}
@@ -0,0 +1,139 @@
+use crate::{
+    APDArgs,
+    error::{AadiffError, Result},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How an output was made, for audit trails: the full command line, a checksum of
+/// each input file, and when the run started.
+#[derive(Debug, Serialize)]
+pub(crate) struct Provenance {
+    command_line: Vec<String>,
+    inputs:       Vec<Input>,
+    timestamp:    String,
+}
+
+#[derive(Debug, Serialize)]
+struct Input {
+    path: String,
+    /// None for stdin, which is only read as the run goes
+    sha256: Option<String>,
+}
+
+impl Provenance {
+    /// Checksums the input FASTA and any config file named by `args`.
+    pub(crate) fn of(args: &APDArgs) -> Result<Self> {
+        let checksum = |path: &Path| {
+            Input::of(path).map_err(|source| AadiffError::Read {
+                kind: "input to checksum",
+                path: path.to_path_buf(),
+                source,
+            })
+        };
+        let mut inputs = vec![match &args.input_fasta {
+            Some(path) => checksum(path)?,
+            None => Input {
+                path:   "stdin".to_string(),
+                sha256: None,
+            },
+        }];
+        if let Some(path) = &args.config {
+            inputs.push(checksum(path)?);
+        }
+
+        Ok(Provenance {
+            command_line: std::env::args().collect(),
+            inputs,
+            timestamp: utc_timestamp(SystemTime::now()),
+        })
+    }
+
+    /// The provenance as comment lines to go before a delimited header, e.g.,
+    /// `# timestamp: 2024-05-01T12:00:00Z`.
+    pub(crate) fn comment_lines(&self, line_ending: &str) -> String {
+        let command_line = self
+            .command_line
+            .iter()
+            .map(|arg| {
+                if arg.contains(char::is_whitespace) {
+                    format!("{arg:?}")
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut lines = format!(
+            "# {} {}{line_ending}\n# command: {command_line}{line_ending}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        for input in &self.inputs {
+            match &input.sha256 {
+                Some(sha256) => lines.push_str(&format!("# input: {} sha256:{sha256}{line_ending}\n", input.path)),
+                None => lines.push_str(&format!("# input: {}{line_ending}\n", input.path)),
+            }
+        }
+        lines.push_str(&format!("# timestamp: {}{line_ending}\n", self.timestamp));
+        lines
+    }
+}
+
+impl Input {
+    fn of(path: &Path) -> io::Result<Self> {
+        Ok(Input {
+            path:   path.display().to_string(),
+            sha256: Some(sha256(path)?),
+        })
+    }
+}
+
+/// The SHA-256 checksum of a file, in hex.
+pub(crate) fn sha256(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Formats a time as RFC 3339 in UTC, converting days since the epoch to a civil
+/// date after Howard Hinnant's `civil_from_days`.
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, time_of_day) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    )
+}
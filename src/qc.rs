@@ -0,0 +1,42 @@
+/// Data quality of an aligned nucleotide sequence.
+pub(crate) struct QcMetrics {
+    /// Fraction of non-gap bases that are ambiguous, e.g., `N` or `R`
+    pub(crate) ambiguity: f64,
+    /// Fraction of alignment columns that are gaps
+    pub(crate) gaps: f64,
+    /// Length of the longest run of ambiguous bases
+    pub(crate) longest_run: usize,
+}
+
+impl QcMetrics {
+    /// Measures a sequence, counting `-` and any of `gap_chars` as gaps.
+    pub(crate) fn of(sequence: &[u8], gap_chars: &[u8]) -> Self {
+        let mut gaps = 0;
+        let mut ambiguous = 0;
+        let mut run = 0;
+        let mut longest_run = 0;
+        for &base in sequence {
+            match base.to_ascii_uppercase() {
+                b'-' => gaps += 1,
+                _ if gap_chars.contains(&base) => gaps += 1,
+                b'A' | b'C' | b'G' | b'T' | b'U' => run = 0,
+                _ => {
+                    ambiguous += 1;
+                    run += 1;
+                    longest_run = longest_run.max(run);
+                }
+            }
+        }
+
+        let bases = sequence.len() - gaps;
+        QcMetrics {
+            ambiguity: if bases > 0 { ambiguous as f64 / bases as f64 } else { 0.0 },
+            gaps:      if sequence.is_empty() {
+                0.0
+            } else {
+                gaps as f64 / sequence.len() as f64
+            },
+            longest_run,
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    APDArgs, DeletionPolicy, RestrictMode,
+    align::score,
+    annotation::{Regions, Watchlist},
+    cells::residue_cell,
+    error::Result,
+    frameshift::find_frameshifts,
+    get_valid_range,
+    numbering::Positions,
+    segments::Segment,
+    sparse::{Baseline, QuerySequence},
+    translate::{PartialCodonPolicy, Recoding, ambiguity_code, is_canonical, is_partially_gapped, stands_for, translate},
+};
+use std::{ops::Range, sync::Arc};
+use tracing::debug;
+use zoe::{data::fasta::FastaNT, prelude::*};
+
+/// A query, translated and checked for what can be compared.
+pub(crate) struct ValidSeq {
+    pub(crate) name:        String,
+    pub(crate) sequence:    QuerySequence,
+    pub(crate) valid_range: Range<usize>,
+    /// From the first to the last codon that is neither X nor a gap
+    pub(crate) called:         Range<usize>,
+    pub(crate) frameshifts:    Vec<Range<usize>>,
+    pub(crate) premature_stop: Option<usize>,
+    pub(crate) missing:        Vec<Range<usize>>,
+}
+
+impl ValidSeq {
+    /// Whether the codon at `i` is compared: within the valid range and not part of a
+    /// segment the sequence has no data for.
+    pub(crate) fn compares(&self, i: usize) -> bool {
+        self.valid_range.contains(&i) && !self.missing.iter().any(|m| m.contains(&i))
+    }
+}
+
+/// Per-query counts and notes gathered while comparing positions.
+#[derive(Default)]
+pub(crate) struct Tally {
+    pub(crate) flagged:       Vec<String>,
+    pub(crate) synonymous:    usize,
+    pub(crate) nonsynonymous: usize,
+    pub(crate) private:       usize,
+    pub(crate) compared:      usize,
+    pub(crate) identical:     usize,
+    pub(crate) domains:       Vec<usize>,
+    pub(crate) reversions:    Vec<String>,
+}
+
+/// Translates queries and finds where in them they can be compared with the reference.
+pub(crate) struct Translator<'a> {
+    pub(crate) args:        &'a APDArgs,
+    pub(crate) recoding:    &'a Recoding<'a>,
+    pub(crate) ref_codons:  &'a [u8],
+    pub(crate) ref_orf_end: usize,
+    pub(crate) restriction: Option<RestrictMode>,
+    pub(crate) segments:    &'a [Segment],
+    /// The reference that queries are stored as differences from, in low-memory mode
+    pub(crate) baseline: Option<Arc<Baseline>>,
+}
+
+impl Translator<'_> {
+    /// Finds the frameshifts in a query's bases, and the segments it has no data for.
+    pub(crate) fn annotate(&self, bases: &[u8]) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+        let frameshifts = find_frameshifts(self.ref_codons, bases);
+        let missing = self
+            .segments
+            .iter()
+            .filter(|s| {
+                bases[s.codons.start * 3..s.codons.end * 3]
+                    .iter()
+                    .all(|&b| matches!(b, b'-' | b'N'))
+            })
+            .map(|s| s.codons.clone())
+            .collect::<Vec<_>>();
+        (frameshifts, missing)
+    }
+
+    /// Finds the codons of a query that are compared, and any premature stop in it.
+    pub(crate) fn validate(
+        &self, name: String, sequence: QuerySequence, (frameshifts, missing): (Vec<Range<usize>>, Vec<Range<usize>>),
+    ) -> Result<ValidSeq> {
+        let valid_range = get_valid_range(
+            (0..sequence.len()).map(|i| sequence.residue(i)),
+            RestrictMode::restricts_queries(self.restriction),
+        )?;
+        let called = if self.args.diff.mark_terminal_missing {
+            get_valid_range((0..sequence.len()).map(|i| sequence.residue(i)), true).unwrap_or(0..0)
+        } else {
+            0..sequence.len()
+        };
+        let premature_stop = valid_range
+            .clone()
+            .find(|&i| self.recoding.residue(i, sequence.codon(i), sequence.residue(i)) == b'*')
+            .filter(|&i| i < self.ref_orf_end);
+        debug!(
+            "Read '{name}', comparable over codons {}-{}",
+            valid_range.start + 1,
+            valid_range.end
+        );
+
+        Ok(ValidSeq {
+            name,
+            sequence,
+            valid_range,
+            called,
+            frameshifts,
+            premature_stop,
+            missing,
+        })
+    }
+
+    /// Stores a query to be compared, in full or, in low-memory mode, as its
+    /// differences from the reference.
+    pub(crate) fn store(&self, FastaNT { name, sequence }: FastaNT) -> Result<ValidSeq> {
+        let args = self.args;
+        let found = self.annotate(sequence.as_bytes());
+        // Codons are translated as they are compared, except when stored sparsely.
+        let sequence = match &self.baseline {
+            Some(baseline) => QuerySequence::sparse(
+                baseline,
+                &translate(sequence.as_bytes(), !args.strict_translation, args.partial_codon_policy),
+                &sequence,
+            ),
+            None => QuerySequence::Full {
+                codons:  sequence,
+                resolve: !args.strict_translation,
+                partial: args.partial_codon_policy,
+            },
+        };
+        self.validate(name, sequence, found)
+    }
+}
+
+/// The codon every query is compared at.
+pub(crate) struct Site<'a> {
+    pub(crate) i:         usize,
+    pub(crate) label:     &'a str,
+    pub(crate) ref_aa:    u8,
+    pub(crate) ref_codon: [u8; 3],
+    pub(crate) masked:    bool,
+}
+
+/// What queries are compared with the reference by, the same at every codon.
+pub(crate) struct Comparison<'a> {
+    pub(crate) args:      &'a APDArgs,
+    pub(crate) recoding:  &'a Recoding<'a>,
+    pub(crate) reference: &'a AminoAcids,
+    pub(crate) ref_range: Range<usize>,
+    pub(crate) positions: &'a Positions<'a>,
+    pub(crate) watchlist: Option<&'a Watchlist>,
+    pub(crate) ancestor:  Option<&'a AminoAcids>,
+    pub(crate) domains:   Option<&'a Regions>,
+    /// Whether mutations are recorded for an emergence report
+    pub(crate) emergence: bool,
+}
+
+impl Comparison<'_> {
+    /// Compares a query at a codon, counting it in the query's tally. Gives the
+    /// query's cell, if it differs or is a synonymous change, and the mutation
+    /// to record for the emergence report.
+    pub(crate) fn compare(&self, site: &Site, seq: &ValidSeq, tally: &mut Tally) -> (Option<String>, Option<String>) {
+        let Comparison { args, positions, .. } = self;
+        let &Site {
+            i,
+            label,
+            ref_aa,
+            ref_codon,
+            masked,
+        } = site;
+        let ValidSeq {
+            sequence, frameshifts, ..
+        } = seq;
+        let compared = seq.compares(i);
+        let codon = sequence.codon(i);
+        let aa = self.recoding.residue(i, codon, sequence.residue(i));
+        let codon_change = args.diff.synonymous
+            && !masked
+            && compared
+            && codon != ref_codon
+            && is_canonical(codon)
+            && is_canonical(ref_codon);
+
+        if codon_change {
+            if ref_aa == aa {
+                tally.synonymous += 1;
+            } else {
+                tally.nonsynonymous += 1;
+            }
+        }
+
+        let skipped = args.partial_codon_policy == PartialCodonPolicy::Skip
+            && (is_partially_gapped(codon) || is_partially_gapped(ref_codon));
+
+        // In protein input, B, Z, and J match either residue they stand for.
+        let ambiguous_match = args.protein
+            && (stands_for(codon, ref_aa)
+                || stands_for(ref_codon, aa)
+                || (codon == ref_codon && ambiguity_code(codon).is_some()));
+
+        if compared && !masked && !skipped && ambiguous_match {
+            tally.compared += 1;
+            tally.identical += 1;
+        } else if compared && !masked && !skipped && aa != b'X' && ref_aa != b'X' && (aa, ref_aa) != (b'-', b'-') {
+            tally.compared += 1;
+            tally.identical += usize::from(aa == ref_aa);
+        }
+
+        let conservative = args.diff.min_blosum_penalty.is_some_and(|penalty| {
+            !matches!(aa, b'-' | b'X') && !matches!(ref_aa, b'-' | b'X') && score(ref_aa, aa) > -penalty
+        });
+
+        let unknown = args.diff.ambiguous_matches_reference && aa == b'X';
+
+        // The codons compared before and after `i` that a run of deletions could span.
+        let neighbor = |k: usize| !(positions.by_reference() && self.reference[k] == b'-');
+        let deleted = |k: usize| seq.compares(k) && sequence.residue(k) == b'-';
+        let hidden_deletion = aa == b'-'
+            && match args.diff.deletions {
+                DeletionPolicy::Report => false,
+                DeletionPolicy::Ignore => true,
+                DeletionPolicy::Collapse => (self.ref_range.start..i).rev().find(|&k| neighbor(k)).is_some_and(deleted),
+            };
+
+        if compared && ref_aa != aa && !skipped && !conservative && !unknown && !hidden_deletion && !ambiguous_match {
+            let mut cell = if masked {
+                "masked".to_string()
+            } else if frameshifts.iter().any(|f| f.contains(&i)) {
+                "fs".to_string()
+            } else if !seq.called.contains(&i) {
+                "missing".to_string()
+            } else {
+                residue_cell(aa, codon, args)
+            };
+
+            let mutation = (!masked
+                && self.emergence
+                && !matches!(cell.as_str(), "fs" | "missing")
+                && !cell.starts_with('X')
+                && !cell.contains('/'))
+            .then(|| format!("{}{label}{cell}", ref_aa as char));
+
+            if !masked && let Some(mutation) = self.watchlist.and_then(|w| w.find(label, ref_aa, &cell)) {
+                tally.flagged.push(mutation.to_string());
+                cell.push('!');
+            }
+
+            if !masked
+                && let Some(ancestor) = self.ancestor
+                && ancestor[i] == aa
+                && !matches!(aa, b'X' | b'-')
+                && cell != "fs"
+            {
+                tally.reversions.push(format!("{}{label}{}", ref_aa as char, aa as char));
+                cell.push('<');
+            }
+
+            if !masked && let Some(domains) = self.domains {
+                for d in domains.covering(positions.numeric(i)) {
+                    tally.domains[d] += 1;
+                }
+            }
+            if args.diff.deletions == DeletionPolicy::Collapse && cell.starts_with("del") {
+                let mut end = i;
+                for k in (i + 1..self.ref_range.end).filter(|&k| neighbor(k)) {
+                    if !deleted(k) {
+                        break;
+                    }
+                    end = k;
+                }
+                if end > i {
+                    cell = cell.replacen("del", &format!("del{label}-{}", positions.label(end)), 1);
+                }
+            }
+
+            (Some(cell), mutation)
+        } else if codon_change && ref_aa == aa {
+            (Some("syn".to_string()), None)
+        } else {
+            (None, None)
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{
+    APDArgs, NumberBy, ReferenceMode,
+    align::codon_align,
+    consensus::majority_consensus,
+    error::{AadiffError, Result},
+    faidx::{IndexedFasta, read_or_build},
+    fasta::FastaLines,
+    get_valid_range,
+    mmap::{FastaRecord, MappedFasta},
+    open_error,
+    progress::Progress,
+    qc::QcMetrics,
+    segments::{Segment, join_segments, read_segment_map, split_segments},
+    translate::back_translate,
+};
+use either::Either;
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{self, BufReader, stdin},
+    path::Path,
+    sync::atomic::Ordering,
+};
+use tracing::{debug, warn};
+use zoe::{data::fasta::FastaNT, prelude::*};
+
+/// The names of the records to read with `--include`, which always covers the
+/// ancestor and the extra references.
+pub(crate) fn included(args: &APDArgs) -> Result<Option<HashSet<String>>> {
+    let Some(path) = args.include.as_deref() else {
+        return Ok(None);
+    };
+    let mut names = read_names(path)?;
+    names.extend(args.diff.ancestor.iter().chain(&args.diff.extra_references).cloned());
+    Ok(Some(names))
+}
+
+/// Maps the input file with `--mmap`, unless it is read through its index.
+pub(crate) fn open_mapped(args: &APDArgs) -> Result<Option<MappedFasta>> {
+    match &args.input_fasta {
+        Some(path) if args.mmap && !args.fai => Ok(Some(MappedFasta::open(path).map_err(open_error(path))?)),
+        _ => Ok(None),
+    }
+}
+
+/// Opens the records to read, from the indexed, mapped or plain input file, from
+/// memory, or from stdin, and leaves out those not included. The first record, the
+/// reference, is always read.
+pub(crate) fn open<'r>(
+    args: &'r APDArgs, input: Option<&'r [u8]>, mapped: Option<&'r MappedFasta>, included: Option<&'r HashSet<String>>,
+    reading: &'r Progress,
+) -> Result<impl Iterator<Item = io::Result<FastaSeq>> + use<'r>> {
+    let reader = match (&args.input_fasta, mapped) {
+        (Some(file_path), _) if args.fai => {
+            let entries = read_or_build(file_path)?
+                .into_iter()
+                .enumerate()
+                .filter(|(k, e)| *k == 0 || included.is_some_and(|names| names.contains(&e.name)))
+                .map(|(_, e)| e)
+                .collect();
+            Either::Right(Either::Right(
+                IndexedFasta::open(file_path, entries).map_err(open_error(file_path))?,
+            ))
+        }
+        (Some(_), Some(mapped)) => Either::Right(Either::Left(mapped.clone())),
+        (Some(file_path), None) => Either::Left(FastaLines::new(BufReader::new(Either::Left(
+            OpenOptions::new().read(true).open(file_path).map_err(open_error(file_path))?,
+        )))),
+        (None, _) => match input {
+            Some(input) => Either::Left(FastaLines::new(BufReader::new(Either::Right(Either::Right(input))))),
+            None => Either::Left(FastaLines::new(BufReader::new(Either::Right(Either::Left(stdin()))))),
+        },
+    };
+    Ok(reader
+        .inspect(|_| reading.inc())
+        .enumerate()
+        .filter_map(move |(k, record)| match (&record, included) {
+            (Ok(r), Some(names)) if k > 0 && !names.contains(&r.name) => {
+                debug!("Skipping '{}', which is not included", r.name);
+                None
+            }
+            _ => Some(record),
+        }))
+}
+
+/// Reads every record as DNA, recovering from malformed ones as `--skip-malformed` allows.
+pub(crate) fn read_all(args: &APDArgs, reader: impl Iterator<Item = io::Result<FastaSeq>>) -> Result<Vec<FastaNT>> {
+    let mut records = Vec::new();
+    for record in reader {
+        if let Some(record) = recover(args, record)? {
+            records.push(recode(record, args));
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the reference from the mapped input, and borrows the queries from the map,
+/// so that their sequences are not copied until they are gathered into columns.
+pub(crate) fn borrow_queries<'m>(
+    args: &APDArgs, mapped: &'m MappedFasta, included: Option<&HashSet<String>>, reading: &Progress,
+) -> Result<(FastaNT, Vec<FastaRecord<'m>>)> {
+    let mut records = mapped.records();
+    let Some(first) = records.next().transpose().map_err(AadiffError::Input)? else {
+        return Err(AadiffError::Empty("No first record available!".to_string()));
+    };
+    let first = recode(
+        FastaSeq {
+            name:     first.name.into_owned(),
+            sequence: first.sequence.into_owned(),
+        },
+        args,
+    );
+    let mut borrowed = Vec::new();
+    for record in records {
+        reading.inc();
+        let Some(mut r) = recover(args, record)? else {
+            continue;
+        };
+        if included.is_some_and(|names| !names.contains(r.name.as_ref())) {
+            debug!("Skipping '{}', which is not included", r.name);
+            continue;
+        }
+        // Sequences already written as uppercase IUPAC DNA stay borrowed; the rest,
+        // and protein input, are copied to be recoded as the reference is.
+        if args.protein || !r.sequence.iter().all(|b| b"ACGTRYSWKMBDHVN-".contains(b)) {
+            let record = FastaSeq {
+                name:     String::new(),
+                sequence: r.sequence.into_owned(),
+            };
+            r.sequence = Cow::Owned(recode(record, args).sequence.as_bytes().to_vec());
+        }
+        if passes_qc(args, &r.name, &r.sequence)
+            && fits_reference(args, &first.name, first.sequence.len(), &r.name, r.sequence.len())?
+        {
+            borrowed.push(r);
+        }
+    }
+    Ok((first, borrowed))
+}
+
+/// Reads every record, picks the reference they are compared against, and
+/// aligns them first if asked to. In multi-segment mode this is done for each
+/// segment, and the segments are then joined.
+pub(crate) fn read_sequences(
+    args: &APDArgs, reader: impl Iterator<Item = io::Result<FastaSeq>>,
+) -> Result<(FastaNT, Vec<FastaNT>, Vec<Segment>)> {
+    let records = read_all(args, reader)?;
+
+    if args.segments || args.segment_map.is_some() {
+        let map = args.segment_map.as_deref().map(read_segment_map).transpose()?;
+        let segments = split_segments(records, map.as_ref())?
+            .into_iter()
+            .map(|(segment, records)| {
+                let (reference, queries) = pick_reference(args, records)?;
+                Ok((segment, reference, queries))
+            })
+            .collect::<Result<_>>()?;
+        Ok(join_segments(segments))
+    } else {
+        let (reference, queries) = pick_reference(args, records)?;
+        Ok((reference, queries, Vec::new()))
+    }
+}
+
+pub(crate) fn pick_reference(args: &APDArgs, records: Vec<FastaNT>) -> Result<(FastaNT, Vec<FastaNT>)> {
+    match args.reference {
+        ReferenceMode::First => {
+            let mut records = records.into_iter();
+            let Some(dna_reference) = records.next() else {
+                return Err(AadiffError::Empty("No first record available!".to_string()));
+            };
+            let queries = drop_failing_qc(args, records.collect());
+
+            if args.align {
+                let (aligned_reference, aligned_queries) = codon_align(
+                    dna_reference.sequence.as_bytes(),
+                    &queries.iter().map(|q| q.sequence.as_bytes()).collect::<Vec<_>>(),
+                );
+                let queries = queries
+                    .into_iter()
+                    .zip(aligned_queries)
+                    .map(|(q, sequence)| FastaNT {
+                        name:     q.name,
+                        sequence: sequence.into(),
+                    })
+                    .collect();
+                let dna_reference = FastaNT {
+                    name:     dna_reference.name,
+                    sequence: aligned_reference.into(),
+                };
+                Ok((dna_reference, queries))
+            } else {
+                Ok((dna_reference, queries))
+            }
+        }
+        ReferenceMode::Consensus => {
+            if args.align {
+                return Err(AadiffError::Invalid(
+                    "Alignment requires the first record as the reference, not a consensus.".to_string(),
+                ));
+            }
+
+            let queries = drop_failing_qc(args, records);
+            if queries.is_empty() {
+                return Err(AadiffError::Empty("No records available!".to_string()));
+            }
+
+            let consensus = FastaNT {
+                name:     "consensus".to_string(),
+                sequence: majority_consensus(queries.iter().map(|q| q.sequence.as_bytes())).into(),
+            };
+            Ok((consensus, queries))
+        }
+    }
+}
+
+/// Recodes a record as DNA, reading lowercase bases as uppercase or, when
+/// respecting soft-masking, as `N` so that the codons they fall in are ambiguous,
+/// and any of `--gap-chars` as gaps.
+pub(crate) fn recode(mut record: FastaSeq, args: &APDArgs) -> FastaNT {
+    normalize(&mut record.sequence, args);
+    if args.protein {
+        // Residues are compared as the codons they back-translate to.
+        record.sequence = record.sequence.iter().flat_map(|&aa| back_translate(aa)).collect();
+    }
+    record.recode_to_dna()
+}
+
+pub(crate) fn normalize(bases: &mut [u8], args: &APDArgs) {
+    let gaps = args.gap_chars.as_bytes();
+    for base in bases {
+        if base.is_ascii_lowercase() {
+            *base = if args.respect_softmask {
+                if args.protein { b'X' } else { b'N' }
+            } else {
+                base.to_ascii_uppercase()
+            };
+        } else if gaps.contains(base) {
+            *base = b'-';
+        }
+    }
+}
+
+/// Drops the sequences exceeding `--max-ambiguity` or `--max-gaps`, warning about each.
+pub(crate) fn drop_failing_qc(args: &APDArgs, sequences: Vec<FastaNT>) -> Vec<FastaNT> {
+    if args.max_ambiguity.is_none() && args.max_gaps.is_none() {
+        return sequences;
+    }
+
+    sequences
+        .into_iter()
+        .filter(|s| passes_qc(args, &s.name, s.sequence.as_bytes()))
+        .collect()
+}
+
+pub(crate) fn drop_malformed(
+    args: &APDArgs, reference_name: &str, reference_length: usize, queries: Vec<FastaNT>,
+) -> Result<Vec<FastaNT>> {
+    let mut kept = Vec::with_capacity(queries.len());
+    for query in queries {
+        if fits_reference(args, reference_name, reference_length, &query.name, query.sequence.len())? {
+            kept.push(query);
+        }
+    }
+    Ok(kept)
+}
+
+/// The length of the reference in whole codons. A trailing partial codon fails the
+/// run unless `--trim-partial-codon` drops it, with a warning.
+pub(crate) fn whole_codons(args: &APDArgs, reference: &FastaNT) -> Result<usize> {
+    let length = reference.sequence.len();
+    let partial = length % 3;
+    if partial == 0 {
+        return Ok(length);
+    }
+
+    let problem = format!(
+        "The alignment is {length} bases long, which ends in a partial codon of {partial} base{} after codon {}",
+        if partial == 1 { "" } else { "s" },
+        length / 3
+    );
+    if args.trim_partial_codon {
+        warn!("{problem}; ignoring its last {partial} in every record.");
+        Ok(length - partial)
+    } else {
+        Err(AadiffError::Invalid(format!(
+            "{problem}. Check that '{}' starts and ends in frame, or ignore the partial codon with --trim-partial-codon.",
+            reference.name
+        )))
+    }
+}
+
+/// Checks that the reference is fit to compare against: between its first and last
+/// called residues, it should have no ambiguous codons, no gaps unless numbering
+/// by the reference, and no stop before its last codon. Problems are warned about, or fail the run with `--strict`.
+pub(crate) fn check_reference(args: &APDArgs, name: &str, residues: &AminoAcids) -> Result<()> {
+    let Ok(called) = get_valid_range(residues.iter().copied(), true) else {
+        return Err(AadiffError::Invalid(format!(
+            "The reference '{name}' has no residues that are neither X nor gaps."
+        )));
+    };
+
+    let residues = residues.as_bytes();
+    let count = |aa: u8| residues[called.clone()].iter().filter(|&&r| r == aa).count();
+    let mut problems = Vec::new();
+    match count(b'X') {
+        0 => {}
+        1 => problems.push("1 ambiguous codon".to_string()),
+        n => problems.push(format!("{n} ambiguous codons")),
+    }
+    // Reference gaps are where insertions are found when numbering by the reference,
+    // so they are expected then.
+    match count(b'-') {
+        _ if args.diff.insertions || args.diff.number_by == NumberBy::Reference => {}
+        0 => {}
+        1 => problems.push("1 gap codon".to_string()),
+        n => problems.push(format!("{n} gap codons")),
+    }
+    if let Some(stop) = residues[called.start..called.end - 1].iter().position(|&r| r == b'*') {
+        problems.push(format!("a premature stop at codon {}", called.start + stop + 1));
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let problem = format!("The reference '{name}' has {} within it", problems.join(", "));
+    if args.strict {
+        Err(AadiffError::Invalid(format!(
+            "{problem}, which would distort every comparison. Choose another reference, or warn only without --strict."
+        )))
+    } else {
+        warn!("{problem}, which may distort comparisons");
+        Ok(())
+    }
+}
+
+/// Cuts a record down to its first `length` bases.
+pub(crate) fn trim_to(record: FastaNT, length: usize) -> FastaNT {
+    if record.sequence.len() <= length {
+        return record;
+    }
+    FastaNT {
+        name:     record.name,
+        sequence: record.sequence.as_bytes()[..length].to_vec().into(),
+    }
+}
+
+/// Whether a query is as long as the reference, as every aligned query must be. A
+/// query that is not fails the run, naming both lengths, unless `--skip-malformed`
+/// drops it with a warning.
+pub(crate) fn fits_reference(
+    args: &APDArgs, reference_name: &str, reference_length: usize, name: &str, length: usize,
+) -> Result<bool> {
+    if length == reference_length {
+        return Ok(true);
+    }
+
+    let problem = format!("'{name}' is {length} bases long, but the reference '{reference_name}' is {reference_length}");
+    if args.skip_malformed {
+        warn!("Dropping {problem}");
+        args.skipped.fetch_add(1, Ordering::Relaxed);
+        Ok(false)
+    } else {
+        Err(AadiffError::Invalid(format!(
+            "{problem}; the input must be aligned, e.g., with --align, or malformed records skipped with --skip-malformed."
+        )))
+    }
+}
+
+/// A record as read, or, under `--skip-malformed`, `None` for one that could not be
+/// parsed, with a warning. Failing to read the input at all is never skipped.
+pub(crate) fn recover<T>(args: &APDArgs, record: io::Result<T>) -> Result<Option<T>> {
+    match record {
+        Ok(record) => Ok(Some(record)),
+        Err(e) if args.skip_malformed && e.kind() == io::ErrorKind::InvalidData => {
+            warn!("Skipping a record that could not be parsed: {e}");
+            args.skipped.fetch_add(1, Ordering::Relaxed);
+            Ok(None)
+        }
+        Err(e) => Err(AadiffError::Input(e)),
+    }
+}
+
+pub(crate) fn report_skipped(args: &APDArgs) {
+    match args.skipped.load(Ordering::Relaxed) {
+        0 => {}
+        1 => warn!("Skipped 1 malformed record"),
+        n => warn!("Skipped {n} malformed records"),
+    }
+}
+
+/// Whether a sequence is within `--max-ambiguity` and `--max-gaps`, warning if not.
+pub(crate) fn passes_qc(args: &APDArgs, name: &str, bases: &[u8]) -> bool {
+    if args.max_ambiguity.is_none() && args.max_gaps.is_none() {
+        return true;
+    }
+
+    let qc = QcMetrics::of(bases, args.gap_chars.as_bytes());
+    let fails = args.max_ambiguity.is_some_and(|max| qc.ambiguity > max) || args.max_gaps.is_some_and(|max| qc.gaps > max);
+    if fails {
+        warn!(
+            "Dropping '{name}': {:.1}% ambiguous, {:.1}% gaps, longest ambiguous run {}",
+            qc.ambiguity * 100.0,
+            qc.gaps * 100.0,
+            qc.longest_run
+        );
+    }
+    !fails
+}
+
+/// Reads one name per line. Blank lines and `#` comments are skipped.
+pub(crate) fn read_names(path: &Path) -> Result<HashSet<String>> {
+    let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+        kind: "name file",
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
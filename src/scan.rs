@@ -0,0 +1,73 @@
+#[cfg(feature = "nightly")]
+use std::simd::prelude::*;
+
+#[cfg(feature = "nightly")]
+const LANES: usize = 32;
+
+/// Marks with a non-zero byte every base of `query` that differs from `reference`,
+/// comparing [`LANES`] bases at a time. Bases past the end of a shorter query are
+/// marked as differing.
+#[cfg(feature = "nightly")]
+pub(crate) fn mark_differences(reference: &[u8], query: &[u8], differs: &mut [u8]) {
+    let n = reference.len().min(query.len());
+    differs[n..].fill(1);
+
+    let mut d = differs[..n].chunks_exact_mut(LANES);
+    let mut r = reference[..n].chunks_exact(LANES);
+    let mut q = query[..n].chunks_exact(LANES);
+    for ((d, r), q) in (&mut d).zip(&mut r).zip(&mut q) {
+        // Most chunks match entirely, so only differing ones are marked base by base.
+        let ne = u8x32::from_slice(r).simd_ne(u8x32::from_slice(q)).to_bitmask();
+        if ne != 0 {
+            for (k, d) in d.iter_mut().enumerate() {
+                *d |= u8::from(ne >> k & 1 == 1);
+            }
+        }
+    }
+
+    for ((d, r), q) in d.into_remainder().iter_mut().zip(r.remainder()).zip(q.remainder()) {
+        *d |= u8::from(r != q);
+    }
+}
+
+/// Marks with a non-zero byte every base of `query` that differs from `reference`.
+/// Bases past the end of a shorter query are marked as differing.
+#[cfg(not(feature = "nightly"))]
+pub(crate) fn mark_differences(reference: &[u8], query: &[u8], differs: &mut [u8]) {
+    let n = reference.len().min(query.len());
+    differs[n..].fill(1);
+    for ((d, r), q) in differs[..n].iter_mut().zip(reference).zip(query) {
+        *d |= u8::from(r != q);
+    }
+}
+
+/// Whether each codon has any base marked as differing.
+pub(crate) fn variable_codons(differs: &[u8]) -> Vec<bool> {
+    differs.chunks_exact(3).map(|codon| codon != [0, 0, 0]).collect()
+}
+
+/// Whether any codon in a column of consecutive codons differs from `codon`,
+/// comparing a repeating pattern of the codon [`LANES`] bases at a time.
+#[cfg(feature = "nightly")]
+pub(crate) fn column_differs(column: &[u8], codon: [u8; 3]) -> bool {
+    let period: [u8; 3 * LANES] = std::array::from_fn(|k| codon[k % 3]);
+    let patterns = [0, 1, 2].map(|p| u8x32::from_slice(&period[p * LANES..(p + 1) * LANES]));
+
+    let mut chunks = column.chunks_exact(3 * LANES);
+    for chunk in &mut chunks {
+        let differs = patterns
+            .iter()
+            .enumerate()
+            .any(|(p, &pattern)| u8x32::from_slice(&chunk[p * LANES..(p + 1) * LANES]).simd_ne(pattern).any());
+        if differs {
+            return true;
+        }
+    }
+    chunks.remainder().chunks_exact(3).any(|c| c != codon)
+}
+
+/// Whether any codon in a column of consecutive codons differs from `codon`.
+#[cfg(not(feature = "nightly"))]
+pub(crate) fn column_differs(column: &[u8], codon: [u8; 3]) -> bool {
+    column.chunks_exact(3).any(|c| c != codon)
+}
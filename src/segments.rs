@@ -0,0 +1,125 @@
+use crate::error::{AadiffError, Result};
+use std::{collections::HashMap, ops::Range, path::Path};
+use zoe::data::fasta::FastaNT;
+
+/// A segment or gene of a multi-segment input, by its codons in the joined alignment.
+pub(crate) struct Segment {
+    pub(crate) name:   String,
+    pub(crate) codons: Range<usize>,
+}
+
+/// Reads a file of record names, segments, and optionally sample names, separated by
+/// tabs or commas. Samples default to the record name. Blank lines and `#` comments
+/// are skipped.
+pub(crate) fn read_segment_map(path: &Path) -> Result<HashMap<String, (String, String)>> {
+    let contents = std::fs::read_to_string(path).map_err(|source| AadiffError::Read {
+        kind: "segment file",
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut segments = HashMap::new();
+    for (n, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line.split(['\t', ',']).map(str::trim).collect::<Vec<_>>();
+        let (name, segment, sample) = match fields.as_slice() {
+            [name, segment] => (name, segment, name),
+            [name, segment, sample, ..] => (name, segment, sample),
+            _ => {
+                return Err(AadiffError::Parse {
+                    kind:    "Segment file",
+                    path:    path.to_path_buf(),
+                    line:    n + 1,
+                    message: "expected a record name and a segment".to_string(),
+                });
+            }
+        };
+        segments.insert(name.to_string(), (sample.to_string(), segment.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Groups records by segment, in order of first appearance, renaming each record to
+/// its sample. Without a map, headers are split at their last `|`, as in
+/// `A/Texas/50/2012|HA`.
+pub(crate) fn split_segments(
+    records: Vec<FastaNT>, map: Option<&HashMap<String, (String, String)>>,
+) -> Result<Vec<(String, Vec<FastaNT>)>> {
+    let mut segments: Vec<(String, Vec<FastaNT>)> = Vec::new();
+    for FastaNT { name, sequence } in records {
+        let assigned = match map {
+            Some(map) => map.get(&name).cloned(),
+            None => name
+                .rsplit_once('|')
+                .map(|(sample, segment)| (sample.to_string(), segment.to_string())),
+        };
+        let Some((sample, segment)) = assigned else {
+            return Err(AadiffError::Invalid(format!(
+                "Record '{name}' has no segment; name it like 'sample|HA' or list it in a segment file."
+            )));
+        };
+
+        let record = FastaNT { name: sample, sequence };
+        match segments.iter_mut().find(|(s, _)| *s == segment) {
+            Some((_, records)) => records.push(record),
+            None => segments.push((segment, vec![record])),
+        }
+    }
+    Ok(segments)
+}
+
+/// Joins each segment's reference and queries end to end, so that every sample
+/// becomes a single query. Segments are trimmed to whole reference codons, and
+/// samples missing a segment are all gaps there.
+pub(crate) fn join_segments(segments: Vec<(String, FastaNT, Vec<FastaNT>)>) -> (FastaNT, Vec<FastaNT>, Vec<Segment>) {
+    let mut samples: Vec<String> = Vec::new();
+    for (_, _, queries) in &segments {
+        for query in queries {
+            if !samples.contains(&query.name) {
+                samples.push(query.name.clone());
+            }
+        }
+    }
+
+    let reference_name = segments.first().map(|(_, r, _)| r.name.clone()).unwrap_or_default();
+    let mut reference = Vec::new();
+    let mut joined = vec![Vec::new(); samples.len()];
+    let mut layout = Vec::new();
+    for (name, segment_reference, queries) in segments {
+        let length = segment_reference.sequence.len() / 3 * 3;
+        let start = reference.len();
+        reference.extend_from_slice(&segment_reference.sequence.as_bytes()[..length]);
+
+        for (sample, sequence) in samples.iter().zip(&mut joined) {
+            let query = queries
+                .iter()
+                .find(|q| q.name == *sample)
+                .map_or(&[][..], |q| q.sequence.as_bytes());
+            let kept = &query[..query.len().min(length)];
+            sequence.extend_from_slice(kept);
+            sequence.extend(std::iter::repeat_n(b'-', length - kept.len()));
+        }
+
+        layout.push(Segment {
+            name,
+            codons: start / 3..(start + length) / 3,
+        });
+    }
+
+    let reference = FastaNT {
+        name:     reference_name,
+        sequence: reference.into(),
+    };
+    let queries = samples
+        .into_iter()
+        .zip(joined)
+        .map(|(name, sequence)| FastaNT {
+            name,
+            sequence: sequence.into(),
+        })
+        .collect();
+    (reference, queries, layout)
+}
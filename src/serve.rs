@@ -0,0 +1,291 @@
+use crate::{
+    APDArgs, Command,
+    error::{AadiffError, Result},
+    names::printable_name,
+};
+use clap::Parser;
+use serde::Deserialize;
+use std::{
+    io::Read,
+    sync::{
+        Arc, Mutex, PoisonError,
+        mpsc::{TrySendError, sync_channel},
+    },
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+
+const TEXT: &str = "text/plain; charset=utf-8";
+
+/// A record of a JSON request, the reference first.
+#[derive(Deserialize)]
+struct Sequence {
+    name:     String,
+    sequence: String,
+}
+
+/// The records of a JSON request, bare or under `sequences`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Sequences {
+    Wrapped { sequences: Vec<Sequence> },
+    Bare(Vec<Sequence>),
+}
+
+/// Listens on the address given to `aadiff serve`, answering each `POST /diff` with
+/// the difference table of its records as JSON, until the process is stopped.
+/// Requests are answered by a fixed number of workers, with as many more waiting
+/// for one, and any beyond those are turned away until a worker is free.
+pub(crate) fn serve(args: APDArgs) -> Result<()> {
+    let Some(Command::Serve {
+        address,
+        workers,
+        max_body,
+    }) = args.command
+    else {
+        return Ok(());
+    };
+    let server =
+        Server::http(address.as_str()).map_err(|e| AadiffError::Invalid(format!("Cannot listen on '{address}': {e}")))?;
+    info!("Serving difference tables at http://{address}/diff");
+
+    let (queue, waiting) = sync_channel::<Request>(workers.get());
+    let waiting = Arc::new(Mutex::new(waiting));
+    for _ in 0..workers.get() {
+        let waiting = Arc::clone(&waiting);
+        std::thread::spawn(move || {
+            loop {
+                let next = waiting.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                let Ok(request) = next else {
+                    break;
+                };
+                let (method, url) = (request.method().clone(), request.url().to_string());
+                if let Err(e) = respond(request, max_body) {
+                    warn!("Could not answer {method:?} {url}: {e}");
+                }
+            }
+        });
+    }
+
+    for request in server.incoming_requests() {
+        if let Err(TrySendError::Full(request) | TrySendError::Disconnected(request)) = queue.try_send(request) {
+            let busy = reply(503, b"Too many requests at once; try again shortly\n".to_vec(), TEXT);
+            if let Err(e) = request.respond(busy) {
+                warn!("Could not turn away a request: {e}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn respond(mut request: Request, max_body: u64) -> std::io::Result<()> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let response = match (request.method(), path) {
+        (Method::Post, "/diff") => match read_body(&mut request, max_body)? {
+            Some(body) => match diff(parse_query(query), &body, is_json(&request)) {
+                Ok(table) => reply(200, table, "application/json"),
+                Err((status, message)) => reply(status, message.into_bytes(), TEXT),
+            },
+            None => reply(
+                413,
+                format!("Request bodies are limited to {max_body} bytes\n").into_bytes(),
+                TEXT,
+            ),
+        },
+        (_, "/diff") => reply(405, b"Only POST is allowed on /diff\n".to_vec(), TEXT),
+        _ => reply(404, b"Not found; POST records to /diff\n".to_vec(), TEXT),
+    };
+    request.respond(response)
+}
+
+/// Reads the body of a request, or nothing if it is larger than `max_body` bytes.
+fn read_body(request: &mut Request, max_body: u64) -> std::io::Result<Option<Vec<u8>>> {
+    if request.body_length().is_some_and(|length| length as u64 > max_body) {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    request.as_reader().take(max_body + 1).read_to_end(&mut body)?;
+    Ok((body.len() as u64 <= max_body).then_some(body))
+}
+
+fn reply(status: u16, body: Vec<u8>, content_type: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    let mut response = Response::from_data(body).with_status_code(status);
+    if let Ok(header) = Header::from_bytes("Content-Type", content_type) {
+        response = response.with_header(header);
+    }
+    response
+}
+
+/// Whether the body of a request is JSON rather than FASTA, as its content type says.
+fn is_json(request: &Request) -> bool {
+    request
+        .headers()
+        .iter()
+        .any(|h| h.field.equiv("Content-Type") && h.value.as_str().starts_with("application/json"))
+}
+
+/// Computes the difference table of a request's records with the options of its
+/// query string, or gives the status and message to answer with instead.
+fn diff(options: Vec<String>, body: &[u8], json: bool) -> std::result::Result<Vec<u8>, (u16, String)> {
+    let bad_request = |message: String| (400, format!("{message}\n"));
+    let args = APDArgs::try_parse_from(
+        std::iter::once("aadiff".to_string())
+            .chain(options)
+            .chain(["--output-format".to_string(), "json".to_string()]),
+    )
+    .map_err(|e| bad_request(e.to_string()))?;
+    if !args.writes_table() {
+        return Err(bad_request("Only the difference table can be served.".to_string()));
+    }
+    if let Some(path) = args.named_files().next() {
+        return Err(bad_request(format!(
+            "Options naming files, such as '{}', cannot be served.",
+            path.display()
+        )));
+    }
+    if args.threads.is_some() {
+        return Err(bad_request(
+            "The threads are shared by every request, so they cannot be set by one.".to_string(),
+        ));
+    }
+
+    let fasta = if json {
+        to_fasta(body).map_err(bad_request)?
+    } else {
+        body.to_vec()
+    };
+    crate::run_in_memory(args, &fasta).map_err(|e| match e.exit_code() {
+        1 | 3 => bad_request(e.to_string()),
+        _ => (500, format!("{e}\n")),
+    })
+}
+
+/// Writes the records of a JSON request as FASTA.
+fn to_fasta(body: &[u8]) -> std::result::Result<Vec<u8>, String> {
+    let sequences = match serde_json::from_slice(body) {
+        Ok(Sequences::Wrapped { sequences } | Sequences::Bare(sequences)) => sequences,
+        Err(e) => {
+            return Err(format!(
+                "Expected a list of {{\"name\", \"sequence\"}} objects, bare or under \"sequences\": {e}"
+            ));
+        }
+    };
+    let mut fasta = Vec::new();
+    for record in sequences {
+        push_record(&mut fasta, record)?;
+    }
+    Ok(fasta)
+}
+
+/// Writes a record as FASTA, refusing one that would not read back as the same
+/// record: a name or sequence with a control character, e.g., a line break, or a
+/// sequence with a `>`.
+fn push_record(fasta: &mut Vec<u8>, Sequence { name, sequence }: Sequence) -> std::result::Result<(), String> {
+    if name.chars().any(char::is_control) {
+        return Err(format!(
+            "The name '{}' has a control character, which FASTA cannot hold.",
+            printable_name(&name)
+        ));
+    }
+    if sequence.chars().any(|c| c.is_control() || c == '>') {
+        return Err(format!(
+            "The sequence of '{name}' has a control character or '>', which FASTA cannot hold within a record."
+        ));
+    }
+    fasta.extend_from_slice(format!(">{name}\n{sequence}\n").as_bytes());
+    Ok(())
+}
+
+/// Turns a query string into long options, e.g., `deletions=collapse&insertions`
+/// into `--deletions=collapse --insertions`.
+fn parse_query(query: &str) -> Vec<String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => format!("--{}={}", decode(key), decode(value)),
+            None => format!("--{}", decode(pair)),
+        })
+        .collect()
+}
+
+/// Decodes `+` and percent escapes in a part of a query string.
+fn decode(text: &str) -> String {
+    let hex = |b: u8| char::from(b).to_digit(16).map(|d| d as u8);
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (
+            bytes[i],
+            bytes.get(i + 1).copied().and_then(hex),
+            bytes.get(i + 2).copied().and_then(hex),
+        ) {
+            (b'%', Some(high), Some(low)) => {
+                decoded.push(high << 4 | low);
+                i += 2;
+            }
+            (b'+', ..) => decoded.push(b' '),
+            (b, ..) => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_query_options() {
+        assert_eq!(
+            parse_query("deletions=collapse&insertions&&name-format=%7Bname%7D+x"),
+            ["--deletions=collapse", "--insertions", "--name-format={name} x"]
+        );
+        assert!(parse_query("").is_empty());
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(decode("a%2Cb%3d"), "a,b=");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz%4"), "%zz%4");
+        assert_eq!(decode("M%C3%A9xico"), "México");
+    }
+
+    #[test]
+    fn writes_json_records_as_fasta() {
+        let bare = br#"[{"name": "ref", "sequence": "ATG"}, {"name": "q1", "sequence": "ATA"}]"#;
+        assert_eq!(to_fasta(bare).unwrap(), b">ref\nATG\n>q1\nATA\n");
+        let wrapped = br#"{"sequences": [{"name": "ref", "sequence": "ATG"}]}"#;
+        assert_eq!(to_fasta(wrapped).unwrap(), b">ref\nATG\n");
+        assert!(to_fasta(b"{\"name\": \"ref\"}").is_err());
+    }
+
+    #[test]
+    fn refuses_records_that_break_fasta() {
+        let record = |name: &str, sequence: &str| {
+            push_record(
+                &mut Vec::new(),
+                Sequence {
+                    name:     name.to_string(),
+                    sequence: sequence.to_string(),
+                },
+            )
+        };
+        assert!(record("q1", "ATG").is_ok());
+        assert!(record("q1\n>q2", "ATG").is_err());
+        assert!(record("q1\t2021", "ATG").is_err());
+        assert!(record("q1", "ATG\n>q2\nATA").is_err());
+        assert!(record("q1", "AT>G").is_err());
+        assert!(record("q1", "AT\rG").is_err());
+    }
+
+    #[test]
+    fn refuses_to_set_threads() {
+        let (status, _) = diff(vec!["--threads=2".to_string()], b">ref\nATG\n", false).unwrap_err();
+        assert_eq!(status, 400);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    columns::Columns,
+    scan::{mark_differences, variable_codons as marked_codons},
+    translate::{PartialCodonPolicy, translate, translate_resolved},
+};
+use std::sync::Arc;
+use zoe::prelude::*;
+
+/// The reference codons and their translation, shared by every query stored as its
+/// differences from them.
+pub(crate) struct Baseline {
+    pub(crate) residues: AminoAcids,
+    pub(crate) codons:   Nucleotides,
+}
+
+/// A query's codons and their translation, either in full, as one query of shared
+/// column-major storage, or, to save memory, as only the codons that differ from the
+/// reference along with their translations. Codons stored in full are translated
+/// only as they are looked at.
+pub(crate) enum QuerySequence {
+    Full {
+        codons:  Nucleotides,
+        resolve: bool,
+        partial: PartialCodonPolicy,
+    },
+    Column {
+        columns: Arc<Columns>,
+        index:   usize,
+        resolve: bool,
+        partial: PartialCodonPolicy,
+    },
+    Sparse {
+        baseline:    Arc<Baseline>,
+        differences: Vec<(usize, [u8; 3], u8)>,
+    },
+}
+
+impl QuerySequence {
+    /// Keeps only the codons of a query that differ from the baseline, along with
+    /// their translations. Codons past the end of the baseline are dropped.
+    pub(crate) fn sparse(baseline: &Arc<Baseline>, residues: &AminoAcids, codons: &Nucleotides) -> Self {
+        let differences = codons
+            .as_bytes()
+            .chunks_exact(3)
+            .zip(baseline.codons.as_bytes().chunks_exact(3))
+            .enumerate()
+            .filter(|(_, (query, reference))| query != reference)
+            .map(|(i, (query, _))| (i, [query[0], query[1], query[2]], residues[i]))
+            .collect();
+        QuerySequence::Sparse {
+            baseline: Arc::clone(baseline),
+            differences,
+        }
+    }
+
+    fn difference(differences: &[(usize, [u8; 3], u8)], i: usize) -> Option<&(usize, [u8; 3], u8)> {
+        differences
+            .binary_search_by_key(&i, |&(position, ..)| position)
+            .ok()
+            .map(|index| &differences[index])
+    }
+
+    /// The number of whole codons.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            QuerySequence::Full { codons, .. } => codons.len() / 3,
+            QuerySequence::Column { columns, .. } => columns.len(),
+            QuerySequence::Sparse { baseline, .. } => baseline.residues.len(),
+        }
+    }
+
+    /// The amino acid at codon `i`.
+    pub(crate) fn residue(&self, i: usize) -> u8 {
+        match self {
+            &QuerySequence::Full { resolve, partial, .. } | &QuerySequence::Column { resolve, partial, .. } => {
+                translate_resolved(self.codon(i), resolve, partial)
+            }
+            QuerySequence::Sparse { baseline, differences } => {
+                Self::difference(differences, i).map_or(baseline.residues[i], |&(_, _, aa)| aa)
+            }
+        }
+    }
+
+    /// The bases of codon `i`.
+    pub(crate) fn codon(&self, i: usize) -> [u8; 3] {
+        match self {
+            QuerySequence::Full { codons, .. } => [codons[i * 3], codons[i * 3 + 1], codons[i * 3 + 2]],
+            QuerySequence::Column { columns, index, .. } => columns.codon(i, *index),
+            QuerySequence::Sparse { baseline, differences } => Self::difference(differences, i).map_or_else(
+                || {
+                    let codons = &baseline.codons;
+                    [codons[i * 3], codons[i * 3 + 1], codons[i * 3 + 2]]
+                },
+                |&(_, codon, _)| codon,
+            ),
+        }
+    }
+
+    /// The base at nucleotide `j`.
+    pub(crate) fn base(&self, j: usize) -> u8 {
+        self.codon(j / 3)[j % 3]
+    }
+
+    /// Marks with a non-zero byte every base that differs from the reference codons.
+    fn mark_differences(&self, reference: &[u8], differs: &mut [u8]) {
+        match self {
+            QuerySequence::Full { codons, .. } => mark_differences(reference, codons.as_bytes(), differs),
+            QuerySequence::Column { .. } => {
+                for (i, reference) in reference.chunks_exact(3).enumerate() {
+                    for (j, base) in self.codon(i).into_iter().enumerate() {
+                        differs[i * 3 + j] |= u8::from(base != reference[j]);
+                    }
+                }
+            }
+            QuerySequence::Sparse { differences, .. } => {
+                for &(i, codon, _) in differences {
+                    for (j, base) in codon.into_iter().enumerate() {
+                        differs[i * 3 + j] |= u8::from(base != reference[i * 3 + j]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Translates the whole query.
+    pub(crate) fn residues(&self) -> AminoAcids {
+        match self {
+            &QuerySequence::Full {
+                ref codons,
+                resolve,
+                partial,
+            } => translate(codons.as_bytes(), resolve, partial),
+            &QuerySequence::Column { resolve, partial, .. } => (0..self.len())
+                .map(|i| translate_resolved(self.codon(i), resolve, partial))
+                .collect(),
+            QuerySequence::Sparse { baseline, differences } => {
+                let mut residues = baseline.residues.as_bytes().to_vec();
+                for &(i, _, aa) in differences {
+                    residues[i] = aa;
+                }
+                AminoAcids::from(residues)
+            }
+        }
+    }
+}
+
+/// Moves the queries stored in full into shared column-major storage, for a
+/// reference of `codons` codons.
+pub(crate) fn store_by_column(sequences: Vec<&mut QuerySequence>, codons: usize) {
+    if !sequences.iter().any(|s| matches!(s, QuerySequence::Full { .. })) {
+        return;
+    }
+
+    let rows = sequences
+        .iter()
+        .map(|s| match s {
+            QuerySequence::Full { codons, .. } => codons.as_bytes(),
+            _ => &[],
+        })
+        .collect::<Vec<_>>();
+    let columns = Arc::new(Columns::gather(codons, &rows));
+
+    for (index, sequence) in sequences.into_iter().enumerate() {
+        if let &mut QuerySequence::Full { resolve, partial, .. } = sequence {
+            *sequence = QuerySequence::Column {
+                columns: Arc::clone(&columns),
+                index,
+                resolve,
+                partial,
+            };
+        }
+    }
+}
+
+/// Finds the codons where any query differs from the reference, scanning column-major
+/// storage a column at a time and other queries a query at a time.
+pub(crate) fn variable_codons<'a>(reference: &[u8], sequences: impl Iterator<Item = &'a QuerySequence>) -> Vec<bool> {
+    let mut differs = vec![0; reference.len()];
+    let mut columns = None;
+    for sequence in sequences {
+        match sequence {
+            QuerySequence::Column { columns: c, .. } => columns = Some(c),
+            _ => sequence.mark_differences(reference, &mut differs),
+        }
+    }
+
+    let mut variable = marked_codons(&differs);
+    if let Some(columns) = columns {
+        for (i, (v, codon)) in variable.iter_mut().zip(reference.chunks_exact(3)).enumerate() {
+            *v |= columns.differs(i, [codon[0], codon[1], codon[2]]);
+        }
+    }
+    variable
+}
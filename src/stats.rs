@@ -0,0 +1,137 @@
+use crate::{
+    APDArgs, RestrictMode,
+    error::{AadiffError, Result},
+    get_valid_range,
+    translate::translate,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+use zoe::data::fasta::FastaNT;
+
+/// Diversity of the residues observed at one position across the queries.
+pub(crate) struct Diversity {
+    pub(crate) entropy:         f64,
+    pub(crate) distinct:        usize,
+    pub(crate) major_frequency: f64,
+}
+
+impl Diversity {
+    /// Summarizes the observed residues, with the Shannon entropy in bits. Unknown
+    /// residues (`X`) are not counted, and `None` is given when nothing remains.
+    pub(crate) fn of(residues: impl Iterator<Item = u8>) -> Option<Self> {
+        let mut counts = HashMap::new();
+        for aa in residues.filter(|&aa| aa != b'X') {
+            *counts.entry(aa).or_insert(0usize) += 1;
+        }
+
+        let total = counts.values().sum::<usize>();
+        if total == 0 {
+            return None;
+        }
+
+        let entropy = counts
+            .values()
+            .map(|&n| {
+                let p = n as f64 / total as f64;
+                -p * p.log2()
+            })
+            .sum::<f64>();
+        let major = counts.values().copied().max().unwrap_or(0);
+
+        Some(Diversity {
+            entropy:         entropy.max(0.0),
+            distinct:        counts.len(),
+            major_frequency: major as f64 / total as f64,
+        })
+    }
+
+    pub(crate) fn cells(&self) -> [Option<String>; 3] {
+        [
+            Some(format!("{:.4}", self.entropy)),
+            Some(self.distinct.to_string()),
+            Some(format!("{:.4}", self.major_frequency)),
+        ]
+    }
+}
+
+/// Residues counted in a profile, in column order.
+const PROFILE_RESIDUES: &[u8] = b"ACDEFGHIKLMNPQRSTVWY*-";
+
+/// Writes the tab-separated header of a position profile.
+pub(crate) fn write_profile_header(writer: &mut impl Write, line_ending: &str) -> io::Result<()> {
+    let mut header = "position\ttotal".to_string();
+    for &aa in PROFILE_RESIDUES {
+        header.push('\t');
+        header.push(aa as char);
+    }
+    writeln!(writer, "{header}{line_ending}")
+}
+
+/// Writes the count, or frequency, of each residue observed at one position. Unknown
+/// residues (`X`) are not counted.
+pub(crate) fn write_profile_row(
+    writer: &mut impl Write, label: &str, residues: impl Iterator<Item = u8>, frequencies: bool, line_ending: &str,
+) -> io::Result<()> {
+    let mut counts = [0usize; PROFILE_RESIDUES.len()];
+    for aa in residues {
+        if let Some(index) = PROFILE_RESIDUES.iter().position(|&r| r == aa) {
+            counts[index] += 1;
+        }
+    }
+
+    let total = counts.iter().sum::<usize>();
+    let mut row = format!("{label}\t{total}");
+    for n in counts {
+        row.push('\t');
+        if frequencies {
+            row.push_str(&format!("{:.4}", if total > 0 { n as f64 / total as f64 } else { 0.0 }));
+        } else {
+            row.push_str(&n.to_string());
+        }
+    }
+    writeln!(writer, "{row}{line_ending}")
+}
+
+/// Writes the diversity of the queries at every reference position, counting each
+/// query only over its comparable range.
+pub(crate) fn write_stats(
+    reference: &FastaNT, queries: &[FastaNT], args: &APDArgs, writer: &mut impl Write, delim: &str, line_ending: &str,
+) -> Result<()> {
+    let translated = |s: &FastaNT| translate(s.sequence.as_bytes(), !args.strict_translation, args.partial_codon_policy);
+    let reference = translated(reference);
+    let restriction = args.restriction();
+    let mut queries = queries
+        .iter()
+        .map(|q| {
+            let residues = translated(q);
+            let range = get_valid_range(
+                residues.as_bytes().iter().copied(),
+                RestrictMode::restricts_queries(restriction),
+            )?;
+            Ok((residues, range))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let ref_range = get_valid_range(reference.iter().copied(), restriction.is_some())?;
+    RestrictMode::apply(restriction, &ref_range, queries.iter_mut().map(|(_, range)| range));
+
+    writeln!(
+        writer,
+        "position{delim}reference{delim}entropy{delim}distinct{delim}major_frequency{line_ending}"
+    )
+    .map_err(AadiffError::Output)?;
+    for (i, &aa) in reference.as_bytes().iter().enumerate() {
+        let observed = queries
+            .iter()
+            .filter(|(_, range)| range.contains(&i))
+            .filter_map(|(residues, _)| residues.as_bytes().get(i).copied());
+        let mut row = format!("{}{delim}{}", i + 1, aa as char);
+        for cell in Diversity::of(observed).map_or([None, None, None], |d| d.cells()) {
+            row.push_str(delim);
+            row.push_str(cell.as_deref().unwrap_or_default());
+        }
+        writeln!(writer, "{row}{line_ending}").map_err(AadiffError::Output)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use crate::{
+    APDArgs,
+    annotation::Regions,
+    cells::notes_cells,
+    error::{AadiffError, Result},
+    glycosylation::{find_sequons, sequon_changes},
+    insertions::find_insertions,
+    numbering::Positions,
+    output::TableWriter,
+    queries::{Tally, ValidSeq},
+    table::PositionDiff,
+};
+use std::{io::Write, ops::Range};
+use zoe::prelude::*;
+
+/// What the rows after the positions summarize for each query.
+pub(crate) struct Summary<'a> {
+    pub(crate) reference: &'a AminoAcids,
+    pub(crate) ref_range: Range<usize>,
+    /// Where the reference's open reading frame ends, at its first stop
+    pub(crate) ref_orf_end: usize,
+    /// Whether queries are compared only over their valid range
+    pub(crate) restricted: bool,
+    pub(crate) queries:    &'a [ValidSeq],
+    pub(crate) tallies:    &'a [Tally],
+    pub(crate) positions:  &'a Positions<'a>,
+    pub(crate) domains:    Option<&'a Regions>,
+}
+
+impl Summary<'_> {
+    /// Writes a row for each summary asked for, and for each kind of note any query
+    /// has, e.g., its frameshifts.
+    pub(crate) fn write<W: Write>(&self, args: &APDArgs, table: &mut TableWriter<W>) -> Result<()> {
+        let Summary {
+            reference,
+            ref_range,
+            queries,
+            tallies,
+            positions,
+            ..
+        } = self;
+        let mut row = |label: &str, cells| {
+            table
+                .row(PositionDiff::new(label, "", cells, Vec::new()))
+                .map_err(AadiffError::Output)
+        };
+
+        if self.restricted {
+            // Codons outside a query's valid range are left blank, so say how many there are.
+            let missing_start = queries
+                .iter()
+                .map(|s| Some(s.valid_range.start.saturating_sub(ref_range.start).to_string()))
+                .collect::<Vec<_>>();
+            row("missing_start", missing_start)?;
+            let missing_end = queries
+                .iter()
+                .map(|s| Some(ref_range.end.saturating_sub(s.valid_range.end).to_string()))
+                .collect::<Vec<_>>();
+            row("missing_end", missing_end)?;
+        }
+
+        let flagged = tallies.iter().map(|t| t.flagged.clone()).collect::<Vec<_>>();
+        if let Some(cells) = notes_cells(&flagged) {
+            row("flagged", cells)?;
+        }
+
+        let reversions = tallies.iter().map(|t| t.reversions.clone()).collect::<Vec<_>>();
+        if let Some(cells) = notes_cells(&reversions) {
+            row("reversions", cells)?;
+        }
+
+        if args.diff.synonymous {
+            let synonymous = tallies.iter().map(|t| Some(t.synonymous.to_string())).collect::<Vec<_>>();
+            row("synonymous", synonymous)?;
+            let nonsynonymous = tallies.iter().map(|t| Some(t.nonsynonymous.to_string())).collect::<Vec<_>>();
+            row("nonsynonymous", nonsynonymous)?;
+        }
+
+        if args.diff.identity {
+            let identity = tallies
+                .iter()
+                .map(|t| (t.compared > 0).then(|| format!("{:.2}", t.identical as f64 * 100.0 / t.compared as f64)))
+                .collect::<Vec<_>>();
+            row("identity", identity)?;
+        }
+
+        if args.diff.private {
+            let private = tallies.iter().map(|t| Some(t.private.to_string())).collect::<Vec<_>>();
+            row("private", private)?;
+        }
+
+        let frameshift_notes = queries
+            .iter()
+            .map(|s| {
+                s.frameshifts
+                    .iter()
+                    .map(|f| format!("frameshift@{}", positions.label(f.start)))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        if let Some(cells) = notes_cells(&frameshift_notes) {
+            row("frameshift", cells)?;
+        }
+
+        let truncation_notes = queries
+            .iter()
+            .map(|s| {
+                s.premature_stop
+                    .map(|stop| {
+                        let lost = (stop..self.ref_orf_end).filter(|&i| reference[i] != b'-').count();
+                        format!("stop@{}:-{lost}", positions.label(stop))
+                    })
+                    .into_iter()
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        if let Some(cells) = notes_cells(&truncation_notes) {
+            row("truncation", cells)?;
+        }
+
+        if let Some(domains) = self.domains {
+            let domain_notes = tallies
+                .iter()
+                .map(|t| {
+                    t.domains
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &n)| n > 0)
+                        .map(|(d, n)| format!("{}:{n}", domains.name(d)))
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            if let Some(cells) = notes_cells(&domain_notes) {
+                row("domains", cells)?;
+            }
+        }
+
+        if args.diff.glycosylation {
+            let ref_sequons = find_sequons(reference.as_bytes());
+            let sequon_notes = queries
+                .iter()
+                .map(|s| {
+                    let (gained, lost) =
+                        sequon_changes(&ref_sequons, &find_sequons(s.sequence.residues().as_bytes()), &s.valid_range);
+                    gained
+                        .into_iter()
+                        .map(|i| format!("+N{}", positions.label(i)))
+                        .chain(lost.into_iter().map(|i| format!("-N{}", positions.label(i))))
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            if let Some(cells) = notes_cells(&sequon_notes) {
+                row("glycosylation", cells)?;
+            }
+        }
+
+        if args.diff.insertions {
+            let insertion_notes = queries
+                .iter()
+                .map(|s| {
+                    find_insertions(reference, &s.sequence.residues(), &s.valid_range)
+                        .into_iter()
+                        .map(|(anchor, residues)| {
+                            let anchor = anchor.map_or_else(|| positions.offset().to_string(), |i| positions.label(i));
+                            format!("ins{anchor}:{residues}")
+                        })
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            if let Some(cells) = notes_cells(&insertion_notes) {
+                row("insertions", cells)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// The difference table: the reference and query column names, the names of any
+/// extra annotation columns, and its rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffTable {
+    pub columns: Vec<String>,
+    pub extras:  Vec<String>,
+    pub rows:    Vec<PositionDiff>,
+}
+
+/// One row of the difference table: a position, or a per-sequence summary such as
+/// `identity`, with the reference cell, one cell per query, and any extra cells.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionDiff {
+    pub label:     String,
+    pub reference: CellValue,
+    pub cells:     Vec<CellValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extras: Vec<CellValue>,
+    /// Named sets of notes on each query cell, e.g., the BLOSUM62 score of each
+    /// substitution.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<(String, Vec<CellValue>)>,
+}
+
+/// A cell of the difference table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CellValue {
+    /// Nothing to report, e.g., a query matching the reference.
+    #[default]
+    Empty,
+    /// A difference or annotation as written, e.g., `K`, `del`, or `I/L!`.
+    Text(String),
+}
+
+impl CellValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            CellValue::Empty => None,
+            CellValue::Text(text) => Some(text),
+        }
+    }
+}
+
+impl From<Option<String>> for CellValue {
+    fn from(cell: Option<String>) -> Self {
+        cell.map_or(CellValue::Empty, CellValue::Text)
+    }
+}
+
+impl From<&str> for CellValue {
+    /// Reads an empty string as an empty cell.
+    fn from(cell: &str) -> Self {
+        if cell.is_empty() {
+            CellValue::Empty
+        } else {
+            CellValue::Text(cell.to_string())
+        }
+    }
+}
+
+impl PositionDiff {
+    pub fn new(label: &str, reference: &str, cells: Vec<Option<String>>, extras: Vec<Option<String>>) -> Self {
+        PositionDiff {
+            label:     label.to_string(),
+            reference: CellValue::from(reference),
+            cells:     cells.into_iter().map(CellValue::from).collect(),
+            extras:    extras.into_iter().map(CellValue::from).collect(),
+            notes:     Vec::new(),
+        }
+    }
+
+    pub fn with_notes(mut self, notes: Vec<(&str, Vec<Option<String>>)>) -> Self {
+        self.notes = notes
+            .into_iter()
+            .map(|(key, notes)| (key.to_string(), notes.into_iter().map(CellValue::from).collect()))
+            .collect();
+        self
+    }
+}
+
+impl DiffTable {
+    /// Every cell of a row by its column name, reference first, with any extra
+    /// cells the row leaves off as empty.
+    pub fn fields<'a>(&'a self, row: &'a PositionDiff) -> impl Iterator<Item = (&'a str, &'a CellValue)> {
+        let extras = row.extras.iter().chain(std::iter::repeat(&CellValue::Empty));
+        self.columns
+            .iter()
+            .zip(std::iter::once(&row.reference).chain(&row.cells))
+            .chain(self.extras.iter().zip(extras))
+            .map(|(column, cell)| (column.as_str(), cell))
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{
+    APDArgs,
+    data::STANDARD_CODE,
+    error::{AadiffError, Result},
+    records::{recode, recover, report_skipped},
+};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{io::Write, ops::RangeInclusive};
+use zoe::{data::fasta::FastaNT, prelude::*};
+
+/// How codons with one or two gap characters are translated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartialCodonPolicy {
+    /// As a deletion
+    Del,
+    /// As an unknown amino acid
+    #[default]
+    X,
+    /// As unknown, and never reported as a difference
+    Skip,
+}
+
+/// The canonical bases represented by an IUPAC nucleotide code.
+pub fn expand_base(base: u8) -> &'static [u8] {
+    match base {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    }
+}
+
+fn base_index(base: u8) -> usize {
+    match base {
+        b'T' | b'U' => 0,
+        b'C' => 1,
+        b'A' => 2,
+        _ => 3,
+    }
+}
+
+/// Translates a codon made of canonical bases.
+fn translate_canonical(codon: [u8; 3]) -> u8 {
+    STANDARD_CODE[base_index(codon[0]) * 16 + base_index(codon[1]) * 4 + base_index(codon[2])]
+}
+
+/// Whether a codon is made only of canonical bases.
+pub fn is_canonical(codon: [u8; 3]) -> bool {
+    codon.iter().all(|b| b"ACGTU".contains(b))
+}
+
+/// Translates a codon, giving `*` for stops, `-` for a fully gapped codon, and `X`
+/// for anything else that is not made of canonical bases.
+pub fn translate_codon(codon: [u8; 3]) -> u8 {
+    if codon == *b"---" {
+        b'-'
+    } else if is_canonical(codon) {
+        translate_canonical(codon)
+    } else {
+        b'X'
+    }
+}
+
+/// Whether a codon has some, but not all, of its bases gapped.
+pub fn is_partially_gapped(codon: [u8; 3]) -> bool {
+    codon.contains(&b'-') && codon != *b"---"
+}
+
+/// Translates a single codon. When `resolve` is set, an ambiguous codon whose every
+/// expansion encodes the same amino acid, e.g., `CTN`, is translated to it instead of `X`.
+pub fn translate_resolved(codon: [u8; 3], resolve: bool, partial: PartialCodonPolicy) -> u8 {
+    match translate_codon(codon) {
+        b'X' if partial == PartialCodonPolicy::Del && is_partially_gapped(codon) => b'-',
+        b'X' if resolve => match expand_codon(codon).as_deref() {
+            Some(&[aa]) => aa,
+            _ => b'X',
+        },
+        aa => aa,
+    }
+}
+
+/// Translates aligned nucleotides codon by codon, ignoring any trailing partial codon,
+/// as [`translate_resolved`] does.
+pub fn translate(sequence: &[u8], resolve: bool, partial: PartialCodonPolicy) -> AminoAcids {
+    sequence
+        .chunks_exact(3)
+        .map(|c| translate_resolved([c[0], c[1], c[2]], resolve, partial))
+        .collect()
+}
+
+/// The codon a residue of protein input is compared as: one encoding it, or, for `B`,
+/// `Z`, and `J`, the degenerate codon encoding exactly the residues it stands for.
+/// Any other letter is unknown, as `NNN` is.
+pub(crate) fn back_translate(residue: u8) -> [u8; 3] {
+    match residue {
+        b'A' => *b"GCT",
+        b'C' => *b"TGT",
+        b'D' => *b"GAT",
+        b'E' => *b"GAA",
+        b'F' => *b"TTT",
+        b'G' => *b"GGT",
+        b'H' => *b"CAT",
+        b'I' => *b"ATT",
+        b'K' => *b"AAA",
+        b'L' => *b"CTT",
+        b'M' => *b"ATG",
+        b'N' => *b"AAT",
+        b'P' => *b"CCT",
+        b'Q' => *b"CAA",
+        b'R' => *b"CGT",
+        b'S' => *b"TCT",
+        b'T' => *b"ACT",
+        b'V' => *b"GTT",
+        b'W' => *b"TGG",
+        b'Y' => *b"TAT",
+        b'B' => *b"RAY",
+        b'Z' => *b"SAR",
+        b'J' => *b"MTH",
+        b'*' => *b"TAA",
+        b'-' => *b"---",
+        _ => *b"NNN",
+    }
+}
+
+/// The letter of protein input, `B`, `Z`, or `J`, that a codon was back-translated
+/// from, if it stands for either of two residues.
+pub(crate) fn ambiguity_code(codon: [u8; 3]) -> Option<u8> {
+    match &codon {
+        b"RAY" => Some(b'B'),
+        b"SAR" => Some(b'Z'),
+        b"MTH" => Some(b'J'),
+        _ => None,
+    }
+}
+
+/// Whether `aa` is one of the residues that the ambiguity code back-translated to
+/// `codon` stands for, e.g., `D` or `N` for `B`.
+pub(crate) fn stands_for(codon: [u8; 3], aa: u8) -> bool {
+    ambiguity_code(codon).is_some() && expand_codon(codon).is_some_and(|residues| residues.contains(&aa))
+}
+
+/// Returns every distinct amino acid a possibly-degenerate codon could encode,
+/// sorted alphabetically with stops last. Codons containing gaps or non-IUPAC
+/// characters have no expansion.
+pub fn expand_codon(codon: [u8; 3]) -> Option<Vec<u8>> {
+    let [b1, b2, b3] = codon.map(expand_base);
+    if b1.is_empty() || b2.is_empty() || b3.is_empty() {
+        return None;
+    }
+
+    let mut residues = Vec::new();
+    for &x in b1 {
+        for &y in b2 {
+            for &z in b3 {
+                let aa = translate_canonical([x, y, z]);
+                if !residues.contains(&aa) {
+                    residues.push(aa);
+                }
+            }
+        }
+    }
+    residues.sort_unstable_by_key(|&aa| (aa == b'*', aa));
+    Some(residues)
+}
+
+/// Counts how many of the canonical codons a possibly-degenerate codon expands to
+/// encode each amino acid, in the order of [`expand_codon`], along with the total
+/// number of canonical codons.
+pub fn expand_codon_counts(codon: [u8; 3]) -> Option<(Vec<(u8, usize)>, usize)> {
+    let residues = expand_codon(codon)?;
+    let [b1, b2, b3] = codon.map(expand_base);
+
+    let mut counts = residues.into_iter().map(|aa| (aa, 0)).collect::<Vec<_>>();
+    for &x in b1 {
+        for &y in b2 {
+            for &z in b3 {
+                let aa = translate_canonical([x, y, z]);
+                if let Some((_, n)) = counts.iter_mut().find(|(r, _)| *r == aa) {
+                    *n += 1;
+                }
+            }
+        }
+    }
+    Some((counts, b1.len() * b2.len() * b3.len()))
+}
+
+/// Stop codons read as the rare amino acids they encode at given codons, 1-based in
+/// the alignment: `TGA` as selenocysteine (`U`) and `TAG` as pyrrolysine (`O`).
+pub(crate) struct Recoding<'a> {
+    pub(crate) selenocysteine: &'a [RangeInclusive<isize>],
+    pub(crate) pyrrolysine:    &'a [RangeInclusive<isize>],
+}
+
+impl Recoding<'_> {
+    /// The residue of the codon at index `i` translated as `aa`, recoded if it is a
+    /// stop at one of the given codons.
+    pub(crate) fn residue(&self, i: usize, codon: [u8; 3], aa: u8) -> u8 {
+        if aa != b'*' {
+            return aa;
+        }
+        let at = |positions: &[RangeInclusive<isize>]| positions.iter().any(|r| r.contains(&(i as isize + 1)));
+        match codon.map(|b| if b == b'U' { b'T' } else { b }) {
+            [b'T', b'G', b'A'] if at(self.selenocysteine) => b'U',
+            [b'T', b'A', b'G'] if at(self.pyrrolysine) => b'O',
+            _ => aa,
+        }
+    }
+
+    /// Recodes translated residues, given the codons they were translated from.
+    pub(crate) fn residues(&self, codons: &[u8], residues: AminoAcids) -> AminoAcids {
+        if self.selenocysteine.is_empty() && self.pyrrolysine.is_empty() {
+            return residues;
+        }
+        residues
+            .iter()
+            .enumerate()
+            .map(|(i, &aa)| self.residue(i, [codons[i * 3], codons[i * 3 + 1], codons[i * 3 + 2]], aa))
+            .collect()
+    }
+}
+
+/// Writes the translation of every record as FASTA, exactly as the records are
+/// translated for comparison.
+pub(crate) fn write_translations(
+    reader: impl Iterator<Item = std::io::Result<FastaSeq>>, args: &APDArgs, writer: &mut impl Write,
+) -> Result<()> {
+    for record in reader {
+        let Some(record) = recover(args, record)? else {
+            continue;
+        };
+        let FastaNT { name, sequence } = recode(record, args);
+        let residues = translate(sequence.as_bytes(), !args.strict_translation, args.partial_codon_policy);
+        // Protein input is written back as read, with its ambiguity codes.
+        let residues = residues
+            .as_bytes()
+            .iter()
+            .zip(sequence.as_bytes().chunks_exact(3))
+            .map(|(&aa, c)| {
+                let code = args.protein.then(|| ambiguity_code([c[0], c[1], c[2]])).flatten();
+                code.unwrap_or(aa)
+            })
+            .collect::<Vec<_>>();
+        writeln!(writer, ">{name}\n{}", String::from_utf8_lossy(&residues)).map_err(AadiffError::Output)?;
+    }
+    report_skipped(args);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_rna_codons() {
+        assert_eq!(translate_codon(*b"AUG"), b'M');
+        assert_eq!(translate_codon(*b"UUU"), b'F');
+        assert_eq!(translate_codon(*b"UGG"), b'W');
+        assert_eq!(translate_codon(*b"UAA"), b'*');
+        assert_eq!(translate(b"AUGUGUUAG", false, PartialCodonPolicy::X).as_bytes(), b"MC*");
+    }
+
+    #[test]
+    fn back_translates_protein_input() {
+        for &aa in b"ACDEFGHIKLMNPQRSTVWY*-" {
+            assert_eq!(translate_codon(back_translate(aa)), aa);
+        }
+        assert_eq!(back_translate(b'O'), *b"NNN");
+        for (code, residues) in [(b'B', b"DN"), (b'Z', b"EQ"), (b'J', b"IL")] {
+            let codon = back_translate(code);
+            assert_eq!(ambiguity_code(codon), Some(code));
+            assert_eq!(expand_codon(codon).unwrap(), residues);
+            assert!(residues.iter().all(|&aa| stands_for(codon, aa)));
+        }
+        assert!(!stands_for(back_translate(b'B'), b'E'));
+        assert!(!stands_for(*b"RAY", b'X'));
+        assert!(!stands_for(*b"NNN", b'A'));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    APDArgs,
+    error::{AadiffError, Result},
+    records::{recover, report_skipped},
+    translate::expand_base,
+};
+use std::{collections::HashSet, io::Write};
+use tracing::info;
+use zoe::prelude::*;
+
+/// Checks each record without comparing any, writing a line per problem: a name
+/// seen before, a length differing from the first record's or not a whole number
+/// of codons, or a character that is neither a nucleotide nor a gap, i.e., `-` or
+/// any of `--gap-chars`. Protein input is checked for amino acids, and has no codons
+/// to count. Gives the number of problems found.
+pub(crate) fn write_validation(
+    reader: impl Iterator<Item = std::io::Result<FastaSeq>>, args: &APDArgs, writer: &mut impl Write, line_ending: &str,
+) -> Result<usize> {
+    // Records are aligned to one another unless they are to be aligned here or hold
+    // different segments.
+    let aligned = !args.align && !args.segments && args.segment_map.is_none();
+    let gaps = args.gap_chars.as_bytes();
+    let mut names = HashSet::new();
+    let mut first_length = None;
+    let mut records = 0;
+    let mut problems = 0;
+    let mut report = |name: &str, problem: String| {
+        problems += 1;
+        write!(writer, "{name}\t{problem}{line_ending}")
+    };
+
+    for record in reader {
+        let Some(FastaSeq { name, sequence }) = recover(args, record)? else {
+            continue;
+        };
+        records += 1;
+        let length = sequence.len();
+        let first_length = *first_length.get_or_insert(length);
+
+        if !names.insert(name.clone()) {
+            report(&name, "name is not unique".to_string()).map_err(AadiffError::Output)?;
+        }
+        if aligned && length != first_length {
+            report(
+                &name,
+                format!("length {length} differs from the first record's {first_length}"),
+            )
+            .map_err(AadiffError::Output)?;
+        }
+        if !args.align && !args.protein && length % 3 != 0 {
+            report(&name, format!("length {length} is not a whole number of codons")).map_err(AadiffError::Output)?;
+        }
+        let mut invalid = sequence
+            .iter()
+            .enumerate()
+            .filter(|&(_, &b)| b != b'-' && !gaps.contains(&b) && !is_residue(args, b.to_ascii_uppercase()));
+        if let Some((i, &b)) = invalid.next() {
+            let more = match invalid.count() {
+                0 => String::new(),
+                n => format!(" and {n} more"),
+            };
+            report(
+                &name,
+                format!(
+                    "'{}' at position {} is not {}{more}",
+                    b.escape_ascii(),
+                    i + 1,
+                    if args.protein { "an amino acid" } else { "a nucleotide" }
+                ),
+            )
+            .map_err(AadiffError::Output)?;
+        }
+    }
+
+    if records == 0 {
+        return Err(AadiffError::Empty("No records available!".to_string()));
+    }
+    report_skipped(args);
+    info!("Checked {records} records and found {problems} problems");
+    Ok(problems)
+}
+
+/// Whether a character is a nucleotide, or in protein input an amino acid, a stop,
+/// or one of the codes for either of two.
+fn is_residue(args: &APDArgs, b: u8) -> bool {
+    if args.protein {
+        b.is_ascii_uppercase() || b == b'*'
+    } else {
+        !expand_base(b).is_empty()
+    }
+}
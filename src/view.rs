@@ -0,0 +1,258 @@
+use crate::{
+    APDArgs, Command,
+    error::{AadiffError, Result},
+    iter::DiffIter,
+    output::CellKind,
+    table::{CellValue, PositionDiff},
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Cell, Row, Table, TableState},
+};
+use std::io;
+
+/// The width of the reference and query columns, beyond which cells are cut off.
+const COLUMN_WIDTH: u16 = 12;
+
+/// The rows skipped by Page Up and Page Down.
+const PAGE: usize = 20;
+
+/// Computes the difference table for `aadiff view` and opens it in the viewer.
+pub(crate) fn view(mut args: APDArgs) -> Result<()> {
+    if let Some(Command::View(diff)) = args.command.take() {
+        args.diff = *diff;
+    }
+    let rows = DiffIter::new(args)?;
+    let columns = rows.columns().to_vec();
+    let rows = rows.collect::<Result<Vec<_>>>()?;
+
+    let mut viewer = Viewer::new(columns, rows);
+    let mut terminal = ratatui::init();
+    let result = viewer.run(&mut terminal);
+    ratatui::restore();
+    result.map_err(AadiffError::Output)
+}
+
+/// What is being typed at the prompt.
+#[derive(Clone, Copy)]
+enum Prompt {
+    Position,
+    Sequence,
+    Mutation,
+}
+
+/// The difference table, scrolled and filtered in the terminal.
+struct Viewer {
+    /// Sequence column names, reference first
+    columns: Vec<String>,
+    rows:    Vec<PositionDiff>,
+    /// Parts of the position labels and query names to show
+    position: String,
+    sequence: String,
+    /// A mutation, e.g., `N142S`, or a cell, e.g., `del`, to jump to
+    mutation:      String,
+    shown_rows:    Vec<usize>,
+    shown_queries: Vec<usize>,
+    /// The first of the shown queries in view, scrolling sideways
+    first_query: usize,
+    state:       TableState,
+    prompt:      Option<(Prompt, String)>,
+    message:     String,
+}
+
+impl Viewer {
+    fn new(columns: Vec<String>, rows: Vec<PositionDiff>) -> Self {
+        let mut viewer = Viewer {
+            columns,
+            rows,
+            position:      String::new(),
+            sequence:      String::new(),
+            mutation:      String::new(),
+            shown_rows:    Vec::new(),
+            shown_queries: Vec::new(),
+            first_query:   0,
+            state:         TableState::default(),
+            prompt:        None,
+            message:       String::new(),
+        };
+        viewer.filter();
+        viewer
+    }
+
+    fn filter(&mut self) {
+        self.shown_rows = (0..self.rows.len())
+            .filter(|&r| self.rows[r].label.contains(&self.position))
+            .collect();
+        self.shown_queries = (0..self.columns.len().saturating_sub(1))
+            .filter(|&q| self.columns[q + 1].contains(&self.sequence))
+            .collect();
+        self.first_query = 0;
+        self.state.select((!self.shown_rows.is_empty()).then_some(0));
+    }
+
+    /// Selects the next shown row, wrapping around, where a shown query has the
+    /// mutation, and scrolls that query into view.
+    fn next_match(&mut self) {
+        let wanted = self.mutation.to_ascii_uppercase();
+        if wanted.is_empty() || self.shown_rows.is_empty() {
+            return;
+        }
+
+        let start = self.state.selected().map_or(0, |i| i + 1);
+        for k in 0..self.shown_rows.len() {
+            let at = (start + k) % self.shown_rows.len();
+            let row = &self.rows[self.shown_rows[at]];
+            let reference = row.reference.as_str().unwrap_or_default();
+            let found = self.shown_queries.iter().position(|&q| {
+                row.cells.get(q).and_then(CellValue::as_str).is_some_and(|cell| {
+                    let cell = cell.to_ascii_uppercase();
+                    cell == wanted || format!("{reference}{}{cell}", row.label).to_ascii_uppercase() == wanted
+                })
+            });
+            if let Some(column) = found {
+                self.message = format!(
+                    "{} at {} in {}",
+                    self.mutation,
+                    row.label,
+                    self.columns[self.shown_queries[column] + 1]
+                );
+                self.state.select(Some(at));
+                self.first_query = column;
+                return;
+            }
+        }
+        self.message = format!("No {} found", self.mutation);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if let Some((prompt, mut text)) = self.prompt.take() {
+                match key.code {
+                    KeyCode::Enter => match prompt {
+                        Prompt::Position => {
+                            self.position = text;
+                            self.filter();
+                        }
+                        Prompt::Sequence => {
+                            self.sequence = text;
+                            self.filter();
+                        }
+                        Prompt::Mutation => {
+                            self.mutation = text;
+                            self.next_match();
+                        }
+                    },
+                    KeyCode::Esc => {}
+                    KeyCode::Backspace => {
+                        text.pop();
+                        self.prompt = Some((prompt, text));
+                    }
+                    KeyCode::Char(c) => {
+                        text.push(c);
+                        self.prompt = Some((prompt, text));
+                    }
+                    _ => self.prompt = Some((prompt, text)),
+                }
+                continue;
+            }
+
+            self.message.clear();
+            let selected = self.state.selected().unwrap_or(0);
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+                KeyCode::PageDown => self.state.select(Some(selected.saturating_add(PAGE))),
+                KeyCode::PageUp => self.state.select(Some(selected.saturating_sub(PAGE))),
+                KeyCode::Home | KeyCode::Char('g') => self.state.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.state.select_last(),
+                KeyCode::Right | KeyCode::Char('l') => {
+                    self.first_query = (self.first_query + 1).min(self.shown_queries.len().saturating_sub(1));
+                }
+                KeyCode::Left | KeyCode::Char('h') => self.first_query = self.first_query.saturating_sub(1),
+                KeyCode::Char('p') => self.prompt = Some((Prompt::Position, self.position.clone())),
+                KeyCode::Char('s') => self.prompt = Some((Prompt::Sequence, self.sequence.clone())),
+                KeyCode::Char('/') => self.prompt = Some((Prompt::Mutation, String::new())),
+                KeyCode::Char('n') => self.next_match(),
+                KeyCode::Esc => {
+                    self.position.clear();
+                    self.sequence.clear();
+                    self.filter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, status_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let label_width = self.rows.iter().map(|r| r.label.len()).max().unwrap_or(0).max(8) as u16;
+        let fit = table_area.width.saturating_sub(label_width + COLUMN_WIDTH + 2) / (COLUMN_WIDTH + 1);
+        let queries = self
+            .shown_queries
+            .iter()
+            .skip(self.first_query)
+            .take(usize::from(fit.max(1)))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let names = ["position", self.columns[0].as_str()]
+            .into_iter()
+            .chain(queries.iter().map(|&q| self.columns[q + 1].as_str()));
+        let header = Row::new(names.map(Cell::from)).bold();
+        let rows = self.shown_rows.iter().map(|&r| {
+            let row = &self.rows[r];
+            let cells = queries.iter().map(|&q| {
+                let cell = row.cells.get(q).and_then(CellValue::as_str).unwrap_or_default();
+                let style = match CellKind::of(cell) {
+                    Some(CellKind::Substitution) => Style::new().fg(Color::Red),
+                    Some(CellKind::Deletion) => Style::new().fg(Color::Blue),
+                    Some(CellKind::Ambiguity) => Style::new().fg(Color::Yellow),
+                    Some(CellKind::Uncompared) => Style::new().dim(),
+                    None => Style::new(),
+                };
+                Cell::from(cell).style(style)
+            });
+            Row::new(
+                [row.label.as_str(), row.reference.as_str().unwrap_or_default()]
+                    .into_iter()
+                    .map(Cell::from)
+                    .chain(cells),
+            )
+        });
+        let widths = [Constraint::Length(label_width)]
+            .into_iter()
+            .chain(std::iter::repeat_n(Constraint::Length(COLUMN_WIDTH), queries.len() + 1));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .row_highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(table, table_area, &mut self.state);
+
+        let status = match &self.prompt {
+            Some((Prompt::Position, text)) => format!("Positions containing: {text}_"),
+            Some((Prompt::Sequence, text)) => format!("Sequences containing: {text}_"),
+            Some((Prompt::Mutation, text)) => format!("Jump to mutation, e.g., N142S: {text}_"),
+            None if !self.message.is_empty() => self.message.clone(),
+            None => format!(
+                "{}/{} positions, {}/{} sequences | arrows scroll, p position, s sequence, / mutation, n next, Esc clear, q quit",
+                self.shown_rows.len(),
+                self.rows.len(),
+                self.shown_queries.len(),
+                self.columns.len().saturating_sub(1)
+            ),
+        };
+        frame.render_widget(Line::from(status), status_area);
+    }
+}
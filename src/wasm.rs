@@ -0,0 +1,13 @@
+use crate::APDArgs;
+use clap::Parser;
+use wasm_bindgen::prelude::*;
+
+/// Diffs a FASTA alignment given as text, taking options as they are written on the
+/// command line, e.g., `["-f", "json", "-r"]`, and giving the table as text.
+#[wasm_bindgen]
+pub fn diff(fasta: &str, options: Vec<String>) -> Result<String, JsError> {
+    let args = APDArgs::try_parse_from(std::iter::once("aadiff".to_string()).chain(options))
+        .map_err(|e| JsError::new(&e.to_string()))?;
+    let table = crate::run_in_memory(args, fasta.as_bytes()).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(String::from_utf8_lossy(&table).into_owned())
+}